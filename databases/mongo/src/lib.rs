@@ -8,7 +8,7 @@ use axum_session::{DatabaseError, DatabasePool, Session, SessionStore};
 use chrono::Utc;
 use mongodb::{
     bson::{doc, Document},
-    Client,
+    Client, IndexModel,
 };
 use serde::{Deserialize, Serialize};
 
@@ -20,13 +20,18 @@ struct MongoSessionData {
     id: String,
     expires: i64,
     session: String,
+    #[serde(default)]
+    version: i64,
+    #[serde(default)]
+    owner: Option<String>,
 }
 impl MongoSessionData {
     fn to_document(&self) -> Document {
         doc! {
             "id": &self.id,
             "expires": self.expires,
-            "session": &self.session
+            "session": &self.session,
+            "version": self.version
         }
     }
 }
@@ -61,6 +66,11 @@ impl DatabasePool for SessionMongoPool {
                 .find_one_and_delete(tmp.to_document())
                 .await
                 .map_err(|err| DatabaseError::GenericDeleteError(err.to_string()))?;
+            // Index the owner so all of a users sessions can be found.
+            let _ = col
+                .create_index(IndexModel::builder().keys(doc! {"owner": 1}).build())
+                .await
+                .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
         }
 
         Ok(())
@@ -117,15 +127,109 @@ impl DatabasePool for SessionMongoPool {
             let filter = doc! {
                 "id": id
             };
+            let update_data = doc! {
+                "$set": {
+                    "id": id.to_string(),
+                    "expires": expires,
+                    "session": session.to_string()
+                },
+                "$inc": {"version": 1}
+            };
+
+            db.collection::<MongoSessionData>(table_name)
+                .update_one(filter, update_data)
+                .upsert(true)
+                .await
+                .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    async fn compare_and_store(
+        &self,
+        id: &str,
+        session: &str,
+        expires: i64,
+        version: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        if let Some(db) = &self.client.default_database() {
+            let col = db.collection::<MongoSessionData>(table_name);
+            // Sessions stored before versioning have no version field which counts as 0.
+            let filter = if version == 0 {
+                doc! {
+                    "id": id,
+                    "$or": [{"version": 0}, {"version": {"$exists": false}}]
+                }
+            } else {
+                doc! {
+                    "id": id,
+                    "version": version
+                }
+            };
             let update_data = doc! {"$set": {
-                "id": id.to_string(),
                 "expires": expires,
-                "session": session.to_string()
+                "session": session.to_string(),
+                "version": version + 1
             }};
 
-            db.collection::<MongoSessionData>(table_name)
+            let result = col
                 .update_one(filter, update_data)
-                .upsert(true)
+                .await
+                .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+            if result.matched_count > 0 {
+                return Ok(true);
+            }
+
+            if version != 0
+                || col
+                    .find_one(doc! {"id": id})
+                    .await
+                    .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?
+                    .is_some()
+            {
+                return Ok(false);
+            }
+
+            col.insert_one(MongoSessionData {
+                id: id.to_string(),
+                expires,
+                session: session.to_string(),
+                version: 1,
+                owner: None,
+            })
+            .await
+            .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+        }
+
+        Ok(true)
+    }
+
+    async fn touch(&self, id: &str, expires: i64, table_name: &str) -> Result<bool, DatabaseError> {
+        if let Some(db) = &self.client.default_database() {
+            let result = db
+                .collection::<MongoSessionData>(table_name)
+                .update_one(doc! {"id": id}, doc! {"$set": {"expires": expires}})
+                .await
+                .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+            return Ok(result.matched_count > 0);
+        }
+
+        Ok(false)
+    }
+
+    async fn set_owner(
+        &self,
+        id: &str,
+        owner: Option<&str>,
+        table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        if let Some(db) = &self.client.default_database() {
+            db.collection::<MongoSessionData>(table_name)
+                .update_one(doc! {"id": id}, doc! {"$set": {"owner": owner}})
                 .await
                 .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
         }
@@ -133,6 +237,54 @@ impl DatabasePool for SessionMongoPool {
         Ok(())
     }
 
+    async fn get_ids_by_owner(
+        &self,
+        owner: &str,
+        table_name: &str,
+    ) -> Result<Vec<String>, DatabaseError> {
+        let mut ids: Vec<String> = Vec::new();
+
+        if let Some(db) = &self.client.default_database() {
+            let filter = doc! {
+                "owner": owner,
+                "expires":
+                    {"$gte": Utc::now().timestamp()}
+            };
+            let mut result = db
+                .collection::<MongoSessionData>(table_name)
+                .find(filter)
+                .await
+                .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+
+            while result
+                .advance()
+                .await
+                .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?
+            {
+                let item = result
+                    .deserialize_current()
+                    .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+                ids.push(item.id);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    async fn load_expiry(&self, id: &str, table_name: &str) -> Result<Option<i64>, DatabaseError> {
+        if let Some(db) = &self.client.default_database() {
+            let result = db
+                .collection::<MongoSessionData>(table_name)
+                .find_one(doc! {"id": id})
+                .await
+                .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+
+            return Ok(result.map(|session| session.expires));
+        }
+
+        Ok(None)
+    }
+
     async fn load(&self, id: &str, table_name: &str) -> Result<Option<String>, DatabaseError> {
         Ok(match &self.client.default_database() {
             Some(db) => {
@@ -31,3 +31,60 @@ pub async fn scan_keys(
 
     Ok(keys)
 }
+
+/// Lua script used to only store a session if its version matches the stored version.
+/// KEYS[1] is the session key, KEYS[2] is the version key and KEYS[3] is the data hash.
+/// ARGV is the session, expires and the expected version.
+pub const COMPARE_AND_STORE: &str = r#"
+local current = redis.call('GET', KEYS[2]) or '0'
+if current ~= ARGV[3] then
+    return 0
+end
+if ARGV[3] ~= '0' and redis.call('EXISTS', KEYS[1]) == 0 then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[1])
+redis.call('EXPIREAT', KEYS[1], ARGV[2])
+redis.call('DEL', KEYS[3])
+redis.call('SET', KEYS[2], tonumber(ARGV[3]) + 1)
+redis.call('EXPIREAT', KEYS[2], ARGV[2])
+return 1
+"#;
+
+/// Lua script used to only store the changed keys of a session that already exists.
+/// The changes are kept within the data hash until the whole session is stored again.
+/// KEYS[1] is the session key, KEYS[2] is the data hash and KEYS[3] is the version key.
+/// ARGV is expires, the number of field and value pairs to set, those pairs and then the fields to delete.
+pub const STORE_PARTIAL: &str = r#"
+if redis.call('EXISTS', KEYS[1]) == 0 then
+    return 0
+end
+local last = 2 + tonumber(ARGV[2]) * 2
+if #ARGV > last then
+    redis.call('HDEL', KEYS[2], unpack(ARGV, last + 1))
+end
+redis.call('HSET', KEYS[2], unpack(ARGV, 3, last))
+redis.call('HSET', KEYS[2], 'version', redis.call('INCR', KEYS[3]))
+redis.call('EXPIREAT', KEYS[1], ARGV[1])
+redis.call('EXPIREAT', KEYS[2], ARGV[1])
+redis.call('EXPIREAT', KEYS[3], ARGV[1])
+return 1
+"#;
+
+/// Gets the key the session's version is stored under.
+/// The session key is used as a hash tag so both keys land on the same cluster slot.
+pub fn version_key(key: &str) -> String {
+    format!("{{{key}}}:version")
+}
+
+/// Gets the key of the hash holding the changes partially stored since the whole session was.
+/// The session key is used as a hash tag so both keys land on the same cluster slot.
+pub fn data_key(key: &str) -> String {
+    format!("{{{key}}}:data")
+}
+
+/// Gets the key of the set holding the session id's of an owner.
+/// It starts with a hash tag so it is never matched when scanning the table's session keys.
+pub fn owner_key(owner: &str, table_name: &str) -> String {
+    format!("{{owner:{owner}}}:{table_name}")
+}
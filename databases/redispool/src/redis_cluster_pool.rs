@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use axum_session::{DatabaseError, DatabasePool, Session, SessionStore};
+use axum_session::{DatabaseError, DatabasePool, Session, SessionPatch, SessionStore};
 use redis_pool::ClusterRedisPool;
 
 ///Redis's Session Helper type for the DatabasePool.
@@ -84,12 +84,210 @@ impl DatabasePool for SessionRedisClusterPool {
             .ignore()
             .expire_at(&id, expires)
             .ignore()
+            .del(super::redis_tools::data_key(&id))
+            .ignore()
+            .incr(super::redis_tools::version_key(&id), 1)
+            .ignore()
+            .expire_at(super::redis_tools::version_key(&id), expires)
+            .ignore()
             .query_async(&mut con)
             .await
             .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
         Ok(())
     }
 
+    async fn compare_and_store(
+        &self,
+        id: &str,
+        session: &str,
+        expires: i64,
+        version: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let id = if table_name.is_empty() {
+            id.to_string()
+        } else {
+            format!("{}:{}", table_name, id)
+        };
+        let mut con = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|err| DatabaseError::GenericAcquire(err.to_string()))?;
+        let stored: bool = redis::Script::new(super::redis_tools::COMPARE_AND_STORE)
+            .key(&id)
+            .key(super::redis_tools::version_key(&id))
+            .key(super::redis_tools::data_key(&id))
+            .arg(session)
+            .arg(expires)
+            .arg(version)
+            .invoke_async(&mut con)
+            .await
+            .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+        Ok(stored)
+    }
+
+    async fn store_partial(
+        &self,
+        id: &str,
+        patch: &SessionPatch,
+        expires: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let id = if table_name.is_empty() {
+            id.to_string()
+        } else {
+            format!("{}:{}", table_name, id)
+        };
+        let mut con = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|err| DatabaseError::GenericAcquire(err.to_string()))?;
+        // Only the changed keys are written into the data hash, which is applied when loading.
+        let (set, del) = patch.hash_fields();
+        let script = redis::Script::new(super::redis_tools::STORE_PARTIAL);
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(&id)
+            .key(super::redis_tools::data_key(&id))
+            .key(super::redis_tools::version_key(&id))
+            .arg(expires)
+            .arg(set.len());
+
+        for (field, value) in &set {
+            invocation.arg(field).arg(value);
+        }
+
+        for field in &del {
+            invocation.arg(field);
+        }
+
+        let stored: bool = invocation
+            .invoke_async(&mut con)
+            .await
+            .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+        Ok(stored)
+    }
+
+    async fn set_owner(
+        &self,
+        id: &str,
+        owner: Option<&str>,
+        table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        // Old owners are not tracked so their sets get cleaned up when they are read.
+        let Some(owner) = owner else {
+            return Ok(());
+        };
+
+        let mut con = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|err| DatabaseError::GenericAcquire(err.to_string()))?;
+        redis::cmd("SADD")
+            .arg(super::redis_tools::owner_key(owner, table_name))
+            .arg(id)
+            .query_async::<()>(&mut con)
+            .await
+            .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+        Ok(())
+    }
+
+    async fn get_ids_by_owner(
+        &self,
+        owner: &str,
+        table_name: &str,
+    ) -> Result<Vec<String>, DatabaseError> {
+        let mut con = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|err| DatabaseError::GenericAcquire(err.to_string()))?;
+        let owner_key = super::redis_tools::owner_key(owner, table_name);
+        let ids: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(&owner_key)
+            .query_async(&mut con)
+            .await
+            .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+        let mut result = Vec::with_capacity(ids.len());
+
+        for id in ids {
+            let key = if table_name.is_empty() {
+                id.clone()
+            } else {
+                format!("{}:{}", table_name, id)
+            };
+            let exists: bool = redis::cmd("EXISTS")
+                .arg(key)
+                .query_async(&mut con)
+                .await
+                .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+
+            if exists {
+                result.push(id);
+            } else {
+                // The session expired or was deleted so remove it from the owners set.
+                redis::cmd("SREM")
+                    .arg(&owner_key)
+                    .arg(&id)
+                    .query_async::<()>(&mut con)
+                    .await
+                    .map_err(|err| DatabaseError::GenericDeleteError(err.to_string()))?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn touch(&self, id: &str, expires: i64, table_name: &str) -> Result<bool, DatabaseError> {
+        let id = if table_name.is_empty() {
+            id.to_string()
+        } else {
+            format!("{}:{}", table_name, id)
+        };
+        let mut con = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|err| DatabaseError::GenericAcquire(err.to_string()))?;
+        // The version key and data hash have to live as long as the session.
+        let (touched, _, _): (bool, bool, bool) = redis::pipe()
+            .atomic() //makes this a transation.
+            .expire_at(&id, expires)
+            .expire_at(super::redis_tools::version_key(&id), expires)
+            .expire_at(super::redis_tools::data_key(&id), expires)
+            .query_async(&mut con)
+            .await
+            .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+        Ok(touched)
+    }
+
+    async fn load_expiry(&self, id: &str, table_name: &str) -> Result<Option<i64>, DatabaseError> {
+        let id = if table_name.is_empty() {
+            id.to_string()
+        } else {
+            format!("{}:{}", table_name, id)
+        };
+        let mut con = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|err| DatabaseError::GenericAcquire(err.to_string()))?;
+        // TTL is negative if the key does not exist or never expires.
+        let ttl: i64 = redis::cmd("TTL")
+            .arg(&id)
+            .query_async(&mut con)
+            .await
+            .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|since| since.as_secs() as i64)
+            .unwrap_or_default();
+        Ok((ttl >= 0).then(|| now + ttl))
+    }
+
     async fn load(&self, id: &str, table_name: &str) -> Result<Option<String>, DatabaseError> {
         let mut con = self
             .pool
@@ -101,12 +299,20 @@ impl DatabasePool for SessionRedisClusterPool {
         } else {
             format!("{}:{}", table_name, id)
         };
-        let result: String = redis::cmd("GET")
-            .arg(id)
+        // Partial writes are kept within the data hash until the whole session is stored again.
+        let (session, fields): (Option<String>, Vec<(String, String)>) = redis::pipe()
+            .get(&id)
+            .hgetall(super::redis_tools::data_key(&id))
             .query_async(&mut con)
             .await
             .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
-        Ok(Some(result))
+
+        match session {
+            Some(session) if !fields.is_empty() => {
+                SessionPatch::apply_hash_fields(&session, fields).map(Some)
+            }
+            session => Ok(session),
+        }
     }
 
     async fn delete_one_by_id(&self, id: &str, table_name: &str) -> Result<(), DatabaseError> {
@@ -121,7 +327,9 @@ impl DatabasePool for SessionRedisClusterPool {
             format!("{}:{}", table_name, id)
         };
         redis::cmd("DEL")
-            .arg(id)
+            .arg(&id)
+            .arg(super::redis_tools::version_key(&id))
+            .arg(super::redis_tools::data_key(&id))
             .query_async(&mut con)
             .await
             .map_err(|err| DatabaseError::GenericDeleteError(err.to_string()))?;
@@ -167,11 +375,30 @@ impl DatabasePool for SessionRedisClusterPool {
                 .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
             for key in keys {
                 redis::cmd("DEL")
-                    .arg(key)
+                    .arg(&key)
+                    .arg(super::redis_tools::version_key(&key))
+                    .arg(super::redis_tools::data_key(&key))
+                    .arg(super::redis_tools::data_key(&key))
                     .query_async(&mut con)
                     .await
                     .map_err(|err| DatabaseError::GenericDeleteError(err.to_string()))?;
             }
+
+            // Remove the owner sets of this table as well.
+            let owner_keys = super::redis_tools::scan_keys(
+                &mut con,
+                &super::redis_tools::owner_key("*", table_name),
+            )
+            .await
+            .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+
+            for key in owner_keys {
+                redis::cmd("DEL")
+                    .arg(&key)
+                    .query_async::<()>(&mut con)
+                    .await
+                    .map_err(|err| DatabaseError::GenericDeleteError(err.to_string()))?;
+            }
         }
         Ok(())
     }
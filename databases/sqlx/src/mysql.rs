@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use axum_session::{DatabaseError, DatabasePool, Session, SessionStore};
+use axum_session::{DatabaseError, DatabasePool, Session, SessionPatch, SessionStore};
 use chrono::Utc;
 use sqlx::{pool::Pool, MySql, MySqlPool};
 
@@ -28,7 +28,9 @@ impl DatabasePool for SessionMySqlPool {
             CREATE TABLE IF NOT EXISTS %%TABLE_NAME%% (
                 id VARCHAR(128) NOT NULL PRIMARY KEY,
                 expires BIGINT NULL,
-                session TEXT NOT NULL
+                session TEXT NOT NULL,
+                version BIGINT NOT NULL DEFAULT 0,
+                owner VARCHAR(255) NULL
             )
         "#
             .replace("%%TABLE_NAME%%", table_name),
@@ -61,6 +63,80 @@ impl DatabasePool for SessionMySqlPool {
             .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
         }
 
+        let (has_version,): (bool,) = sqlx::query_as(
+            &r#"
+            select COUNT(*) > 0
+            from INFORMATION_SCHEMA.COLUMNS
+            WHERE TABLE_NAME = '%%TABLE_NAME%%' and COLUMN_NAME = 'version';
+            "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
+
+        if !has_version {
+            sqlx::query(
+                &r#"
+                    ALTER TABLE %%TABLE_NAME%% ADD COLUMN version BIGINT NOT NULL DEFAULT 0;
+                "#
+                .replace("%%TABLE_NAME%%", table_name),
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
+        }
+
+        let (has_owner,): (bool,) = sqlx::query_as(
+            &r#"
+            select COUNT(*) > 0
+            from INFORMATION_SCHEMA.COLUMNS
+            WHERE TABLE_NAME = '%%TABLE_NAME%%' and COLUMN_NAME = 'owner';
+            "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
+
+        if !has_owner {
+            sqlx::query(
+                &r#"
+                    ALTER TABLE %%TABLE_NAME%% ADD COLUMN owner VARCHAR(255) NULL;
+                "#
+                .replace("%%TABLE_NAME%%", table_name),
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
+        }
+
+        let (has_owner_index,): (bool,) = sqlx::query_as(
+            &r#"
+            select COUNT(*) > 0
+            from INFORMATION_SCHEMA.STATISTICS
+            WHERE TABLE_NAME = '%%TABLE_NAME%%' and INDEX_NAME = '%%INDEX_NAME%%';
+            "#
+            .replace("%%INDEX_NAME%%", &owner_index_name(table_name))
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
+
+        if !has_owner_index {
+            sqlx::query(
+                &r#"
+                    CREATE INDEX %%INDEX_NAME%% ON %%TABLE_NAME%% (owner);
+                "#
+                .replace("%%INDEX_NAME%%", &owner_index_name(table_name))
+                .replace("%%TABLE_NAME%%", table_name),
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
+        }
+
         Ok(())
     }
 
@@ -112,10 +188,11 @@ impl DatabasePool for SessionMySqlPool {
         sqlx::query(
             &r#"
         INSERT INTO %%TABLE_NAME%%
-            (id, session, expires) SELECT ?, ?, ?
+            (id, session, expires, version) SELECT ?, ?, ?, 1
         ON DUPLICATE KEY UPDATE
             expires = VALUES(expires),
-            session = VALUES(session)
+            session = VALUES(session),
+            version = version + 1
     "#
             .replace("%%TABLE_NAME%%", table_name),
         )
@@ -128,6 +205,158 @@ impl DatabasePool for SessionMySqlPool {
         Ok(())
     }
 
+    async fn compare_and_store(
+        &self,
+        id: &str,
+        session: &str,
+        expires: i64,
+        version: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query(
+            &r#"
+        UPDATE %%TABLE_NAME%% SET
+            session = ?,
+            expires = ?,
+            version = version + 1
+        WHERE id = ? AND version = ?
+    "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(session)
+        .bind(expires)
+        .bind(id)
+        .bind(version)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        if result.rows_affected() > 0 {
+            return Ok(true);
+        }
+
+        if version != 0 {
+            return Ok(false);
+        }
+
+        let result = sqlx::query(
+            &r#"
+        INSERT IGNORE INTO %%TABLE_NAME%%
+            (id, session, expires, version) SELECT ?, ?, ?, 1
+    "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(id)
+        .bind(session)
+        .bind(expires)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_owner(
+        &self,
+        id: &str,
+        owner: Option<&str>,
+        table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            &r#"UPDATE %%TABLE_NAME%% SET owner = ? WHERE id = ?"#
+                .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(owner)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+        Ok(())
+    }
+
+    async fn get_ids_by_owner(
+        &self,
+        owner: &str,
+        table_name: &str,
+    ) -> Result<Vec<String>, DatabaseError> {
+        let result: Vec<(String,)> = sqlx::query_as(
+            &r#"
+            SELECT id FROM %%TABLE_NAME%%
+            WHERE owner = ? AND (expires IS NULL OR expires > ?)
+        "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(owner)
+        .bind(Utc::now().timestamp())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+
+        Ok(result.into_iter().map(|(s,)| s).collect())
+    }
+
+    async fn store_partial(
+        &self,
+        id: &str,
+        patch: &SessionPatch,
+        expires: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        // Only the changed keys are sent as a JSON merge patch. Encrypted sessions are skipped.
+        // The version is increased within the column and the JSON so versioned writers see it.
+        let result = sqlx::query(
+            &r#"
+        UPDATE %%TABLE_NAME%% SET
+            session = JSON_SET(JSON_MERGE_PATCH(session, ?), '$.version', version + 1),
+            expires = ?,
+            version = version + 1
+        WHERE id = ? AND JSON_VALID(session)
+    "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(&patch.merge_patch)
+        .bind(expires)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn touch(&self, id: &str, expires: i64, table_name: &str) -> Result<bool, DatabaseError> {
+        let result = sqlx::query(
+            &r#"
+        UPDATE %%TABLE_NAME%% SET expires = ?
+        WHERE id = ?
+    "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(expires)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn load_expiry(&self, id: &str, table_name: &str) -> Result<Option<i64>, DatabaseError> {
+        let result: Option<(Option<i64>,)> = sqlx::query_as(
+            &r#"
+            SELECT expires FROM %%TABLE_NAME%%
+            WHERE id = ?
+        "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+
+        Ok(result.and_then(|(expires,)| expires))
+    }
+
     async fn load(&self, id: &str, table_name: &str) -> Result<Option<String>, DatabaseError> {
         let result: Option<(String,)> = sqlx::query_as(
             &r#"
@@ -203,3 +432,8 @@ impl DatabasePool for SessionMySqlPool {
         false
     }
 }
+
+/// Index names can not contain the schema so we replace the dot.
+fn owner_index_name(table_name: &str) -> String {
+    format!("{}_owner_idx", table_name.replace('.', "_"))
+}
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use axum_session::{DatabaseError, DatabasePool, Session, SessionStore};
+use axum_session::{DatabaseError, DatabasePool, Session, SessionPatch, SessionStore};
 use chrono::Utc;
 use sqlx::{pool::Pool, PgPool, Postgres};
 
@@ -28,7 +28,9 @@ impl DatabasePool for SessionPgPool {
             CREATE TABLE IF NOT EXISTS %%TABLE_NAME%% (
                 "id" VARCHAR(128) NOT NULL PRIMARY KEY,
                 "expires" BIGINT NULL,
-                "session" TEXT NOT NULL
+                "session" TEXT NOT NULL,
+                "version" BIGINT NOT NULL DEFAULT 0,
+                "owner" VARCHAR(255) NULL
             )
         "#
             .replace("%%TABLE_NAME%%", table_name),
@@ -61,6 +63,37 @@ impl DatabasePool for SessionPgPool {
             .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
         }
 
+        sqlx::query(
+            &r#"
+            ALTER TABLE %%TABLE_NAME%% ADD COLUMN IF NOT EXISTS "version" BIGINT NOT NULL DEFAULT 0;
+            "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
+
+        sqlx::query(
+            &r#"
+            ALTER TABLE %%TABLE_NAME%% ADD COLUMN IF NOT EXISTS "owner" VARCHAR(255) NULL;
+            "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
+
+        sqlx::query(
+            &r#"
+            CREATE INDEX IF NOT EXISTS %%INDEX_NAME%% ON %%TABLE_NAME%% ("owner");
+            "#
+            .replace("%%INDEX_NAME%%", &owner_index_name(table_name))
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
+
         Ok(())
     }
 
@@ -111,10 +144,11 @@ impl DatabasePool for SessionPgPool {
         sqlx::query(
             &r#"
         INSERT INTO %%TABLE_NAME%%
-            (id, session, expires) SELECT $1, $2, $3
+            (id, session, expires, version) SELECT $1, $2, $3, 1
         ON CONFLICT(id) DO UPDATE SET
             expires = EXCLUDED.expires,
-            session = EXCLUDED.session
+            session = EXCLUDED.session,
+            version = %%TABLE_NAME%%.version + 1
     "#
             .replace("%%TABLE_NAME%%", table_name),
         )
@@ -127,6 +161,169 @@ impl DatabasePool for SessionPgPool {
         Ok(())
     }
 
+    async fn compare_and_store(
+        &self,
+        id: &str,
+        session: &str,
+        expires: i64,
+        version: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let result = sqlx::query(
+            &r#"
+        UPDATE %%TABLE_NAME%% SET
+            session = $2,
+            expires = $3,
+            version = $4 + 1
+        WHERE id = $1 AND version = $4
+    "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(id)
+        .bind(session)
+        .bind(expires)
+        .bind(version)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        if result.rows_affected() > 0 {
+            return Ok(true);
+        }
+
+        if version != 0 {
+            return Ok(false);
+        }
+
+        let result = sqlx::query(
+            &r#"
+        INSERT INTO %%TABLE_NAME%%
+            (id, session, expires, version) SELECT $1, $2, $3, 1
+        ON CONFLICT(id) DO NOTHING
+    "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(id)
+        .bind(session)
+        .bind(expires)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_owner(
+        &self,
+        id: &str,
+        owner: Option<&str>,
+        table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        sqlx::query(
+            &r#"UPDATE %%TABLE_NAME%% SET owner = $2 WHERE id = $1"#
+                .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(id)
+        .bind(owner)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+        Ok(())
+    }
+
+    async fn get_ids_by_owner(
+        &self,
+        owner: &str,
+        table_name: &str,
+    ) -> Result<Vec<String>, DatabaseError> {
+        let result: Vec<(String,)> = sqlx::query_as(
+            &r#"
+            SELECT id FROM %%TABLE_NAME%%
+            WHERE owner = $1 AND (expires IS NULL OR expires > $2)
+        "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(owner)
+        .bind(Utc::now().timestamp())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+
+        Ok(result.into_iter().map(|(s,)| s).collect())
+    }
+
+    async fn store_partial(
+        &self,
+        id: &str,
+        patch: &SessionPatch,
+        expires: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        // Only the changed keys are sent and merged into the stored JSON. Encrypted sessions are skipped.
+        // The version is increased within the column and the JSON so versioned writers see it.
+        let result = sqlx::query(
+            &r#"
+        UPDATE %%TABLE_NAME%% SET
+            session = jsonb_set(
+                jsonb_set(
+                    session::jsonb || $5::jsonb,
+                    '{data}',
+                    (COALESCE(session::jsonb -> 'data', '{}'::jsonb) || $2::jsonb) - $3::text[]
+                ),
+                '{version}',
+                to_jsonb(version + 1)
+            )::text,
+            expires = $4,
+            version = version + 1
+        WHERE id = $1 AND left(session, 1) = '{'
+    "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(id)
+        .bind(&patch.changed)
+        .bind(&patch.removed)
+        .bind(expires)
+        .bind(&patch.fields)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn touch(&self, id: &str, expires: i64, table_name: &str) -> Result<bool, DatabaseError> {
+        let result = sqlx::query(
+            &r#"
+        UPDATE %%TABLE_NAME%% SET expires = $2
+        WHERE id = $1
+    "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(id)
+        .bind(expires)
+        .execute(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn load_expiry(&self, id: &str, table_name: &str) -> Result<Option<i64>, DatabaseError> {
+        let result: Option<(Option<i64>,)> = sqlx::query_as(
+            &r#"
+            SELECT expires FROM %%TABLE_NAME%%
+            WHERE id = $1
+        "#
+            .replace("%%TABLE_NAME%%", table_name),
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+
+        Ok(result.and_then(|(expires,)| expires))
+    }
+
     async fn load(&self, id: &str, table_name: &str) -> Result<Option<String>, DatabaseError> {
         let result: Option<(String,)> = sqlx::query_as(
             &r#"
@@ -202,3 +399,8 @@ impl DatabasePool for SessionPgPool {
         false
     }
 }
+
+/// Index names can not contain the schema so we replace the dot.
+fn owner_index_name(table_name: &str) -> String {
+    format!("{}_owner_idx", table_name.replace('.', "_"))
+}
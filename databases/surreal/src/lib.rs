@@ -56,7 +56,16 @@ impl<C: Connection> SessionSurrealPool<C> {
 
 #[async_trait]
 impl<C: Connection> DatabasePool for SessionSurrealPool<C> {
-    async fn initiate(&self, _table_name: &str) -> Result<(), DatabaseError> {
+    async fn initiate(&self, table_name: &str) -> Result<(), DatabaseError> {
+        // Tables are created when first used but we still want the owner to be indexed.
+        self.connection
+            .query(format!(
+                "DEFINE INDEX IF NOT EXISTS {table_name}_owner ON TABLE {table_name} FIELDS sessionowner;"
+            ))
+            .await
+            .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?
+            .check()
+            .map_err(|err| DatabaseError::GenericCreateError(err.to_string()))?;
         Ok(())
     }
 
@@ -112,7 +121,7 @@ impl<C: Connection> DatabasePool for SessionSurrealPool<C> {
     ) -> Result<(), DatabaseError> {
         self.connection
         .query(
-            "UPSERT type::thing($table_name, $session_id) SET sessionstore = $store, sessionexpires = $expire, sessionid = $session_id;",
+            "UPSERT type::thing($table_name, $session_id) SET sessionstore = $store, sessionexpires = $expire, sessionid = $session_id, sessionversion = (sessionversion ?? 0) + 1;",
         )
         .bind(("table_name", table_name.to_string()))
         .bind(("session_id", id.to_string()))
@@ -123,6 +132,136 @@ impl<C: Connection> DatabasePool for SessionSurrealPool<C> {
         Ok(())
     }
 
+    async fn compare_and_store(
+        &self,
+        id: &str,
+        session: &str,
+        expires: i64,
+        version: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let mut res = self
+            .connection
+            .query(
+                "UPDATE type::thing($table_name, $session_id) SET sessionstore = $store, sessionexpires = $expire, sessionversion = $version + 1
+                WHERE sessionversion = $version OR (sessionversion = NONE AND $version = 0) RETURN sessionid;",
+            )
+            .bind(("table_name", table_name.to_string()))
+            .bind(("session_id", id.to_string()))
+            .bind(("expire", expires.to_string()))
+            .bind(("store", session.to_string()))
+            .bind(("version", version))
+            .await
+            .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        let updated: Vec<String> = res
+            .take("sessionid")
+            .map_err(|err| DatabaseError::GenericNotSupportedError(err.to_string()))?;
+
+        if !updated.is_empty() {
+            return Ok(true);
+        }
+
+        if version != 0 {
+            return Ok(false);
+        }
+
+        // CREATE fails if the record already exists, which means someone else stored it first.
+        let created = self
+            .connection
+            .query(
+                "CREATE type::thing($table_name, $session_id) SET sessionstore = $store, sessionexpires = $expire, sessionid = $session_id, sessionversion = 1;",
+            )
+            .bind(("table_name", table_name.to_string()))
+            .bind(("session_id", id.to_string()))
+            .bind(("expire", expires.to_string()))
+            .bind(("store", session.to_string()))
+            .await
+            .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?
+            .check();
+
+        match created {
+            Ok(_) => Ok(true),
+            // Remote engines only return the message so the error is matched by it.
+            Err(err) if err.to_string().contains("already exists") => Ok(false),
+            Err(err) => Err(DatabaseError::GenericInsertError(err.to_string())),
+        }
+    }
+
+    async fn touch(&self, id: &str, expires: i64, table_name: &str) -> Result<bool, DatabaseError> {
+        let mut res = self
+            .connection
+            .query(
+                "UPDATE type::thing($table_name, $session_id) SET sessionexpires = $expire RETURN sessionid;",
+            )
+            .bind(("table_name", table_name.to_string()))
+            .bind(("session_id", id.to_string()))
+            .bind(("expire", expires.to_string()))
+            .await
+            .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        let updated: Vec<String> = res
+            .take("sessionid")
+            .map_err(|err| DatabaseError::GenericNotSupportedError(err.to_string()))?;
+
+        Ok(!updated.is_empty())
+    }
+
+    async fn set_owner(
+        &self,
+        id: &str,
+        owner: Option<&str>,
+        table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        self.connection
+            .query("UPDATE type::thing($table_name, $session_id) SET sessionowner = $owner;")
+            .bind(("table_name", table_name.to_string()))
+            .bind(("session_id", id.to_string()))
+            .bind(("owner", owner.map(str::to_string)))
+            .await
+            .map_err(|err| DatabaseError::GenericInsertError(err.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn get_ids_by_owner(
+        &self,
+        owner: &str,
+        table_name: &str,
+    ) -> Result<Vec<String>, DatabaseError> {
+        let mut res = self
+            .connection
+            .query(
+                "SELECT sessionid FROM type::table($table_name)
+                WHERE sessionowner = $owner AND (sessionexpires = NONE OR sessionexpires > $expires);",
+            )
+            .bind(("table_name", table_name.to_string()))
+            .bind(("owner", owner.to_string()))
+            .bind(("expires", Utc::now().timestamp()))
+            .await
+            .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+
+        let ids: Vec<String> = res
+            .take("sessionid")
+            .map_err(|err| DatabaseError::GenericNotSupportedError(err.to_string()))?;
+        Ok(ids)
+    }
+
+    async fn load_expiry(&self, id: &str, table_name: &str) -> Result<Option<i64>, DatabaseError> {
+        let mut res = self
+            .connection
+            .query("SELECT sessionexpires FROM type::thing($table_name, $session_id);")
+            .bind(("table_name", table_name.to_string()))
+            .bind(("session_id", id.to_string()))
+            .await
+            .map_err(|err| DatabaseError::GenericSelectError(err.to_string()))?;
+
+        let expires: Option<String> = res
+            .take("sessionexpires")
+            .map_err(|err| DatabaseError::GenericNotSupportedError(err.to_string()))?;
+        Ok(expires.and_then(|expires| expires.parse().ok()))
+    }
+
     async fn load(&self, id: &str, table_name: &str) -> Result<Option<String>, DatabaseError> {
         let mut res = self
             .connection
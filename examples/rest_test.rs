@@ -2,7 +2,7 @@ use axum::body::Body;
 use axum::http::{Method, Request, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::{routing::get, Router};
-use axum_session::{Key, SessionConfig, SessionLayer, SessionStore};
+use axum_session::{Key, SessionConfig, SessionLayer, SessionStore, SessionTransport};
 use axum_session_surreal::{SessionSurrealPool, SessionSurrealSession};
 use http_body_util::BodyExt;
 use surrealdb::engine::any::{connect, Any};
@@ -27,7 +27,10 @@ async fn main() {
 
     // No need here to specify a table name because redis does not support tables
     // We are also generating a encryption key for storage.
-    let session_config = SessionConfig::default().with_key(Key::generate());
+    // The Session ID is sent within the headers instead of cookies.
+    let session_config = SessionConfig::default()
+        .with_key(Key::generate())
+        .with_transport(SessionTransport::Header);
 
     // create SessionStore and initiate the database tables
     let session_store =
@@ -0,0 +1,327 @@
+use crate::{compression::COMPRESSED_HEADER, SessionConfig, SessionData, SessionError};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+/// The header byte of JSON encoded Sessions. This is the opening brace so
+/// Sessions stored before codecs existed are still detected as JSON.
+pub(crate) const JSON_HEADER: u8 = b'{';
+
+/// A Session as it is stored within the database, given to a `SessionCodec` to serialize.
+/// Its contents are private and can only be serialized and deserialized with serde.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StoredSession(pub(crate) SessionData);
+
+/// Used to Serialize and Deserialize the `StoredSession` stored within the database.
+///
+/// Every codec has its own header byte which is placed at the start of the stored value.
+/// When loading, the header byte is used to find the codec that stored the Session,
+/// so changing the codec does not break Sessions that were stored with the old one.
+/// They get stored with the new codec the next time they are saved.
+///
+/// Binary codecs are base64 encoded after the header byte since Sessions are stored as Strings.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::{SessionCodec, SessionError, StoredSession};
+///
+/// #[derive(Debug)]
+/// struct PrettyJson;
+///
+/// impl SessionCodec for PrettyJson {
+///     fn header(&self) -> u8 {
+///         b'p'
+///     }
+///
+///     fn encode(&self, session: &StoredSession) -> Result<Vec<u8>, SessionError> {
+///         Ok(serde_json::to_vec_pretty(session)?)
+///     }
+///
+///     fn decode(&self, bytes: &[u8]) -> Result<StoredSession, SessionError> {
+///         Ok(serde_json::from_slice(bytes)?)
+///     }
+/// }
+/// ```
+///
+pub trait SessionCodec: Debug + Send + Sync + 'static {
+    /// The unique ASCII byte placed at the start of every Session stored by this codec.
+    /// `~` is reserved for compressed Sessions and `{` for JSON, which the databases edit in
+    /// place for partial writes, so a codec using `{` must store JSON.
+    /// `SessionStore::new` returns an error if the header breaks these rules.
+    fn header(&self) -> u8;
+
+    /// If the encoded bytes need to be base64 encoded to be stored as a String.
+    fn is_binary(&self) -> bool {
+        true
+    }
+
+    /// Serializes the Session into the bytes that get stored after the header byte.
+    fn encode(&self, session: &StoredSession) -> Result<Vec<u8>, SessionError>;
+
+    /// Deserializes the bytes stored after the header byte back into the Session.
+    /// Text codecs using the `{` header are given the whole value, including the header.
+    fn decode(&self, bytes: &[u8]) -> Result<StoredSession, SessionError>;
+}
+
+/// Stores Sessions as JSON. This is the default codec and the only one that
+/// supports partial writes, since the databases edit the JSON in place.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonCodec;
+
+impl SessionCodec for JsonCodec {
+    fn header(&self) -> u8 {
+        JSON_HEADER
+    }
+
+    fn is_binary(&self) -> bool {
+        false
+    }
+
+    fn encode(&self, session: &StoredSession) -> Result<Vec<u8>, SessionError> {
+        Ok(serde_json::to_vec(session)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<StoredSession, SessionError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+/// Stores Sessions as MessagePack.
+#[cfg(feature = "msgpack")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePackCodec;
+
+#[cfg(feature = "msgpack")]
+impl SessionCodec for MessagePackCodec {
+    fn header(&self) -> u8 {
+        b'm'
+    }
+
+    fn encode(&self, session: &StoredSession) -> Result<Vec<u8>, SessionError> {
+        rmp_serde::to_vec_named(session).map_err(codec_error)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<StoredSession, SessionError> {
+        rmp_serde::from_slice(bytes).map_err(codec_error)
+    }
+}
+
+/// Stores Sessions as CBOR.
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborCodec;
+
+#[cfg(feature = "cbor")]
+impl SessionCodec for CborCodec {
+    fn header(&self) -> u8 {
+        b'c'
+    }
+
+    fn encode(&self, session: &StoredSession) -> Result<Vec<u8>, SessionError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(session, &mut bytes).map_err(codec_error)?;
+        Ok(bytes)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<StoredSession, SessionError> {
+        ciborium::from_reader(bytes).map_err(codec_error)
+    }
+}
+
+/// Stores Sessions as bincode.
+#[cfg(feature = "bincode")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BincodeCodec;
+
+#[cfg(feature = "bincode")]
+impl SessionCodec for BincodeCodec {
+    fn header(&self) -> u8 {
+        b'b'
+    }
+
+    fn encode(&self, session: &StoredSession) -> Result<Vec<u8>, SessionError> {
+        bincode::serde::encode_to_vec(session, bincode::config::standard()).map_err(codec_error)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<StoredSession, SessionError> {
+        bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+            .map(|(session, _)| session)
+            .map_err(codec_error)
+    }
+}
+
+#[cfg(any(feature = "msgpack", feature = "cbor", feature = "bincode"))]
+fn codec_error(err: impl std::fmt::Display) -> SessionError {
+    SessionError::GenericNotSupportedError(format!("Session codec error: {err}"))
+}
+
+/// Encodes the Session with the codec and places the codec's header byte in front of it.
+pub(crate) fn encode(
+    codec: &dyn SessionCodec,
+    session: SessionData,
+) -> Result<String, SessionError> {
+    let bytes = codec.encode(&StoredSession(session))?;
+
+    if !codec.is_binary() {
+        let value = String::from_utf8(bytes)?;
+
+        // JSON already starts with its header.
+        return Ok(if codec.header() == JSON_HEADER {
+            value
+        } else {
+            format!("{}{value}", codec.header() as char)
+        });
+    }
+
+    Ok(format!(
+        "{}{}",
+        codec.header() as char,
+        general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+/// Decodes the Session using the codec that matches its header byte.
+/// The configured codec is checked first so custom codecs can be found.
+pub(crate) fn decode(codec: &dyn SessionCodec, value: &str) -> Result<SessionData, SessionError> {
+    let Some(&header) = value.as_bytes().first() else {
+        return Err(SessionError::GenericNotSupportedError(
+            "Session data was empty.".to_owned(),
+        ));
+    };
+
+    let builtin: &[&dyn SessionCodec] = &[
+        &JsonCodec,
+        #[cfg(feature = "msgpack")]
+        &MessagePackCodec,
+        #[cfg(feature = "cbor")]
+        &CborCodec,
+        #[cfg(feature = "bincode")]
+        &BincodeCodec,
+    ];
+
+    let Some(codec) = std::iter::once(codec)
+        .chain(builtin.iter().copied())
+        .find(|codec| codec.header() == header)
+    else {
+        return Err(SessionError::GenericNotSupportedError(format!(
+            "No Session codec found for header byte {:?}. Is its feature enabled?",
+            header as char
+        )));
+    };
+
+    let session = if !codec.is_binary() {
+        codec.decode(if header == JSON_HEADER {
+            value.as_bytes()
+        } else {
+            &value.as_bytes()[1..]
+        })?
+    } else {
+        codec.decode(&general_purpose::STANDARD.decode(&value.as_bytes()[1..])?)?
+    };
+
+    Ok(session.0)
+}
+
+/// Checks the codec's header byte does not collide with compressed Sessions
+/// and that a codec using the JSON header stores JSON.
+pub(crate) fn validate(codec: &dyn SessionCodec) -> Result<(), SessionError> {
+    let header = codec.header();
+
+    if !header.is_ascii() || header as char == COMPRESSED_HEADER {
+        return Err(SessionError::GenericNotSupportedError(format!(
+            "The Session codec header byte {:?} must be ASCII and is reserved if it is '~'.",
+            header as char
+        )));
+    }
+
+    if header == JSON_HEADER {
+        let session = SessionData::new(String::new(), true, &SessionConfig::default());
+
+        if serde_json::from_str::<serde_json::Value>(&encode(codec, session)?).is_err() {
+            return Err(SessionError::GenericNotSupportedError(
+                "Session codecs using the '{' header byte must store JSON.".to_owned(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, encode, validate, JsonCodec, SessionCodec, StoredSession};
+    use crate::{SessionConfig, SessionData, SessionError};
+
+    #[test]
+    fn sessions_decode_from_any_codec() {
+        let config = SessionConfig::default();
+        let mut session = SessionData::new("id".to_owned(), true, &config);
+        session.set("answer", 42);
+
+        let json = encode(&JsonCodec, session.clone()).unwrap();
+        assert!(json.starts_with('{'));
+        assert_eq!(
+            decode(&JsonCodec, &json).unwrap().get::<i32>("answer"),
+            Some(42)
+        );
+
+        #[cfg(feature = "msgpack")]
+        {
+            let value = encode(&super::MessagePackCodec, session.clone()).unwrap();
+            assert_eq!(
+                decode(&JsonCodec, &value).unwrap().get::<i32>("answer"),
+                Some(42)
+            );
+        }
+
+        #[cfg(feature = "cbor")]
+        {
+            let value = encode(&super::CborCodec, session.clone()).unwrap();
+            assert_eq!(
+                decode(&JsonCodec, &value).unwrap().get::<i32>("answer"),
+                Some(42)
+            );
+        }
+
+        #[cfg(feature = "bincode")]
+        {
+            let value = encode(&super::BincodeCodec, session.clone()).unwrap();
+            assert_eq!(
+                decode(&JsonCodec, &value).unwrap().get::<i32>("answer"),
+                Some(42)
+            );
+        }
+
+        assert!(decode(&JsonCodec, "?abc").is_err());
+    }
+
+    #[derive(Debug)]
+    struct TextCodec(u8);
+
+    impl SessionCodec for TextCodec {
+        fn header(&self) -> u8 {
+            self.0
+        }
+
+        fn is_binary(&self) -> bool {
+            false
+        }
+
+        fn encode(&self, session: &StoredSession) -> Result<Vec<u8>, SessionError> {
+            Ok(format!("{:?}", session).into_bytes())
+        }
+
+        fn decode(&self, _bytes: &[u8]) -> Result<StoredSession, SessionError> {
+            Err(SessionError::NoSessionError)
+        }
+    }
+
+    #[test]
+    fn reserved_headers_are_refused() {
+        assert!(validate(&JsonCodec).is_ok());
+        assert!(validate(&TextCodec(b't')).is_ok());
+        assert!(validate(&TextCodec(b'~')).is_err());
+        assert!(validate(&TextCodec(b'{')).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::SessionError;
+#[cfg(any(feature = "zstd", feature = "deflate"))]
+use base64::{engine::general_purpose, Engine as _};
+
+/// Placed at the start of compressed Sessions followed by the algorithms byte.
+/// No codec may use this as its header byte.
+pub(crate) const COMPRESSED_HEADER: char = '~';
+
+/// The compression used on Sessions stored within the database.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::{SessionCompression, SessionConfig};
+///
+/// let config = SessionConfig::default().with_compression(SessionCompression::None);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SessionCompression {
+    /// Sessions are stored as they are.
+    #[default]
+    None,
+    /// Zstandard at the given level. 3 is a good starting point.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+    /// Deflate at the given level from 0 to 9. 6 is a good starting point.
+    #[cfg(feature = "deflate")]
+    Deflate(u32),
+}
+
+/// Compresses the encoded Session if it is at least threshold bytes long.
+/// Smaller Sessions are returned as they are since compressing them rarely saves anything.
+pub(crate) fn compress(
+    compression: SessionCompression,
+    threshold: usize,
+    value: String,
+) -> Result<String, SessionError> {
+    if compression == SessionCompression::None || value.len() < threshold {
+        return Ok(value);
+    }
+
+    match compression {
+        SessionCompression::None => Ok(value),
+        #[cfg(feature = "zstd")]
+        SessionCompression::Zstd(level) => Ok(format!(
+            "{COMPRESSED_HEADER}z{}",
+            general_purpose::STANDARD.encode(zstd::bulk::compress(value.as_bytes(), level)?)
+        )),
+        #[cfg(feature = "deflate")]
+        SessionCompression::Deflate(level) => {
+            use std::io::Write;
+
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::new(level));
+            encoder.write_all(value.as_bytes())?;
+
+            Ok(format!(
+                "{COMPRESSED_HEADER}d{}",
+                general_purpose::STANDARD.encode(encoder.finish()?)
+            ))
+        }
+    }
+}
+
+/// Decompresses the Session if it starts with the `COMPRESSED_HEADER`.
+/// Sessions that were stored without compression are returned as they are.
+pub(crate) fn decompress(value: String) -> Result<String, SessionError> {
+    let Some(rest) = value.strip_prefix(COMPRESSED_HEADER) else {
+        return Ok(value);
+    };
+
+    match rest.split_at_checked(1) {
+        #[cfg(feature = "zstd")]
+        Some(("z", data)) => Ok(String::from_utf8(zstd::stream::decode_all(
+            general_purpose::STANDARD.decode(data)?.as_slice(),
+        )?)?),
+        #[cfg(feature = "deflate")]
+        Some(("d", data)) => {
+            use std::io::Read;
+
+            let mut value = String::new();
+            flate2::read::DeflateDecoder::new(general_purpose::STANDARD.decode(data)?.as_slice())
+                .read_to_string(&mut value)?;
+            Ok(value)
+        }
+        _ => Err(SessionError::GenericNotSupportedError(
+            "Session was compressed with an unsupported algorithm. Is its feature enabled?"
+                .to_owned(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compress, decompress, SessionCompression};
+
+    #[test]
+    fn compressed_sessions_round_trip() {
+        let value = format!("{{\"data\":{{\"key\":\"{}\"}}}}", "a".repeat(2048));
+
+        // Uncompressed and small Sessions are left alone.
+        assert_eq!(
+            compress(SessionCompression::None, 0, value.clone()).unwrap(),
+            value
+        );
+        assert_eq!(decompress(value.clone()).unwrap(), value);
+
+        #[cfg(feature = "zstd")]
+        {
+            let small = compress(SessionCompression::Zstd(3), 1024, "{}".to_owned()).unwrap();
+            assert_eq!(small, "{}");
+
+            let compressed = compress(SessionCompression::Zstd(3), 1024, value.clone()).unwrap();
+            assert!(compressed.len() < value.len());
+            assert_eq!(decompress(compressed).unwrap(), value);
+        }
+
+        #[cfg(feature = "deflate")]
+        {
+            let compressed = compress(SessionCompression::Deflate(6), 1024, value.clone()).unwrap();
+            assert!(compressed.len() < value.len());
+            assert_eq!(decompress(compressed).unwrap(), value);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::{JsonCodec, SessionCodec, SessionCompression};
 use chrono::Duration;
 pub use cookie::{Key, SameSite};
 use std::{
@@ -5,6 +6,7 @@ use std::{
     fmt::{Debug, Formatter, Result},
     sync::Arc,
 };
+use tracing::Level;
 use uuid::Uuid;
 
 /// Mode at which the Session will function As.
@@ -48,6 +50,140 @@ impl SessionMode {
     }
 }
 
+/// How the Session ID is sent between the client and server.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::{SessionConfig, SessionTransport};
+///
+/// let config = SessionConfig::default().with_transport(SessionTransport::Header);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SessionTransport {
+    /// Uses cookies. This is the default and is what browsers should use.
+    #[default]
+    Cookie,
+    /// Uses headers instead of cookies. For rest API requests and responses.
+    Header,
+    /// Uses the cookies, but if no Session cookie was sent the header is used instead.
+    /// Responses only set the one the request used, or both when it sent neither,
+    /// so browsers and rest API clients can be served by the same routes.
+    CookieThenHeader,
+}
+
+impl SessionTransport {
+    /// Checks if cookies are used to send the Session ID.
+    ///
+    pub fn uses_cookies(&self) -> bool {
+        matches!(
+            self,
+            SessionTransport::Cookie | SessionTransport::CookieThenHeader
+        )
+    }
+
+    /// Checks if headers are used to send the Session ID.
+    ///
+    pub fn uses_headers(&self) -> bool {
+        matches!(
+            self,
+            SessionTransport::Header | SessionTransport::CookieThenHeader
+        )
+    }
+}
+
+/// How a Session save is handled when another request or server
+/// already changed the Session within the database.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::{ConflictPolicy, SessionConfig};
+///
+/// let config = SessionConfig::default().with_conflict_policy(ConflictPolicy::RetryWithMerge(3));
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Overwrites whatever is stored in the database without checking its version.
+    /// This is the default and matches how sessions were always saved.
+    LastWriteWins,
+    /// Refuses to overwrite a newer version and returns `SessionError::VersionConflict`.
+    Error,
+    /// Reloads the newer version from the database, reapplies the keys this request
+    /// changed on top of it and tries again up to the given amount of times.
+    /// Returns `SessionError::VersionConflict` if it still could not be saved.
+    RetryWithMerge(usize),
+}
+
+impl ConflictPolicy {
+    /// Checks if the Session's version needs to be compared before saving.
+    ///
+    pub fn is_versioned(&self) -> bool {
+        !matches!(self, ConflictPolicy::LastWriteWins)
+    }
+}
+
+/// What happens when the database can not be reached while loading, saving or removing Sessions.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::{DatabaseFailurePolicy, SessionConfig};
+/// use chrono::Duration;
+///
+/// let config = SessionConfig::default()
+///     .with_failure_policy(DatabaseFailurePolicy::ServeFromMemory(Duration::seconds(5)));
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseFailurePolicy {
+    /// Database errors are returned and the request fails. This is the default.
+    Fail,
+    /// The store enters a degraded mode and serves Sessions from memory. Saves and removals
+    /// are queued and replayed in order once the database responds again, which is checked after
+    /// the given Duration. Sessions that were not loaded into memory start over as new
+    /// Sessions, which never replace the stored Session, and queued writes are lost if the
+    /// server stops while degraded.
+    ServeFromMemory(Duration),
+}
+
+/// The most memory the `SessionStore` keeps Sessions within before evicting the least recently used.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::{MemoryCapacity, SessionConfig};
+///
+/// let config = SessionConfig::default().with_memory_capacity(MemoryCapacity::Entries(50_000));
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryCapacity {
+    /// The most Sessions kept within memory.
+    Entries(usize),
+    /// The most bytes of Sessions kept within memory. This is an estimate based on the size
+    /// of the Session and its keys and values, kept as a running total that is updated when
+    /// a Session is loaded, unloaded or changed by a request.
+    Bytes(usize),
+}
+
+/// What happens when a Session gains an owner who already has the most Sessions allowed by
+/// `SessionConfig::with_max_sessions_per_owner`.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::{OwnerLimitPolicy, SessionConfig};
+///
+/// let config = SessionConfig::default().with_max_sessions_per_owner(3, OwnerLimitPolicy::EvictOldest);
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OwnerLimitPolicy {
+    /// Destroys the owner's oldest Sessions by creation time to make room for the new one.
+    EvictOldest,
+    /// Rolls the Session back to the owner it had before the request without storing it and
+    /// responds with `SessionError::SessionLimitReached` so the login can be refused.
+    RefuseNew,
+}
+
 #[derive(Clone)]
 pub struct CookieAndHeaderConfig {
     /// The Cookie or Header name that contains a boolean for session saving.
@@ -70,6 +206,9 @@ pub struct CookieAndHeaderConfig {
     pub(crate) cookie_secure: bool,
     /// Encyption Key used to sign cookies and header for integrity, and authenticity.
     pub(crate) key: Option<Key>,
+    /// Old signing Keys that are still accepted when verifying cookies and headers.
+    /// Anything verified with one of these gets signed again with `key` on the response.
+    pub(crate) retired_keys: Vec<Key>,
     /// This is used to append __Host- to the front of all Cookie names to prevent sub domain usage.
     /// This will not append to Headers only Cookies. It is enabled by default.
     pub(crate) prefix_with_host: bool,
@@ -77,6 +216,10 @@ pub struct CookieAndHeaderConfig {
     /// and header values. This will help prevent others from spoofing your cookies and headers.
     /// It is enabled by default.
     pub(crate) with_ip_and_user_agent: bool,
+    /// If the Session ID is sent within cookies, headers or both. Default is `SessionTransport::Cookie`.
+    pub(crate) transport: SessionTransport,
+    /// Sends the Session ID header as `Authorization: Bearer <token>` instead of using the session name.
+    pub(crate) bearer_token: bool,
 }
 
 impl Debug for CookieAndHeaderConfig {
@@ -92,7 +235,10 @@ impl Debug for CookieAndHeaderConfig {
             .field("cookie_secure", &self.cookie_secure)
             .field("prefix_with_host", &self.prefix_with_host)
             .field("with_ip_and_user_agent", &self.with_ip_and_user_agent)
+            .field("transport", &self.transport)
+            .field("bearer_token", &self.bearer_token)
             .field("key", &"key hidden")
+            .field("retired_keys", &"keys hidden")
             .finish()
     }
 }
@@ -101,12 +247,27 @@ impl Debug for CookieAndHeaderConfig {
 pub struct DatabaseConfig {
     /// Encyption Key used to encypt Session data stored in the database for confidentiality.
     pub(crate) database_key: Option<Key>,
+    /// Old Encyption Keys that are still used to decrypt Session data encrypted before the current key was set.
+    pub(crate) retired_database_keys: Vec<Key>,
     /// Session Database table name default is sessions.
     pub(crate) table_name: Cow<'static, str>,
     /// This value represents the duration for how often session's data gets purged from the database per request.
     pub(crate) purge_database_update: Duration,
     /// Ignore's the update checks and will always save the session to the database if set to true.
     pub(crate) always_save: bool,
+    /// How to handle a session that was changed in the database since it was loaded.
+    pub(crate) conflict_policy: ConflictPolicy,
+    /// How to handle the database being unavailable.
+    pub(crate) failure_policy: DatabaseFailurePolicy,
+    /// How long after the expiry was last extended before an unchanged session extends it again.
+    /// None stores the whole session on every request like before.
+    pub(crate) touch_interval: Option<Duration>,
+    /// Serializes the Session before it is stored within the database.
+    pub(crate) codec: Arc<dyn SessionCodec>,
+    /// Compresses the serialized Session before it is encrypted and stored.
+    pub(crate) compression: SessionCompression,
+    /// Serialized Sessions smaller than this many bytes are not compressed.
+    pub(crate) compression_threshold: usize,
 }
 
 impl Debug for DatabaseConfig {
@@ -115,7 +276,14 @@ impl Debug for DatabaseConfig {
             .field("table_name", &self.table_name)
             .field("purge_database_update", &self.purge_database_update)
             .field("always_save", &self.always_save)
+            .field("conflict_policy", &self.conflict_policy)
+            .field("failure_policy", &self.failure_policy)
+            .field("touch_interval", &self.touch_interval)
+            .field("codec", &self.codec)
+            .field("compression", &self.compression)
+            .field("compression_threshold", &self.compression_threshold)
             .field("database_key", &"key hidden")
+            .field("retired_database_keys", &"keys hidden")
             .finish()
     }
 }
@@ -139,6 +307,8 @@ pub struct MemoryConfig {
     /// the false positives it can give you can disable it by setting it to false. This will reduce memory usage.
     /// By default this is enabled unless the specific database cant function with it then disabled.
     pub(crate) use_bloom_filters: bool,
+    /// The most Sessions kept within memory before the least recently used are evicted.
+    pub(crate) capacity: Option<MemoryCapacity>,
 }
 
 impl Debug for MemoryConfig {
@@ -148,6 +318,7 @@ impl Debug for MemoryConfig {
             .field("filter_expected_elements", &self.filter_expected_elements)
             .field("use_bloom_filters", &self.use_bloom_filters)
             .field("purge_update", &self.purge_update)
+            .field("capacity", &self.capacity)
             .field(
                 "filter_false_positive_probability",
                 &self.filter_false_positive_probability,
@@ -214,8 +385,20 @@ pub struct SessionConfig {
     /// This is set to the Cookie before sending and to the database before updating/inserting.
     /// Only Set when Long Term is true.
     pub(crate) max_lifespan: Duration,
+    /// Hard cap on how long a Session can exist since it was created no matter how active it is.
+    pub(crate) absolute_lifespan: Option<Duration>,
     /// This is to be used when your handling multiple Parallel Sessions to prevent the next one from unloaded data.
     pub(crate) clear_check_on_load: bool,
+    /// Runs the memory and database sweeps during requests. Disable this when using `SessionStore::spawn_maintenance`.
+    pub(crate) inline_maintenance: bool,
+    /// The most Sessions an owner can have at once and what happens when a new one goes past it.
+    pub(crate) max_sessions_per_owner: Option<(usize, OwnerLimitPolicy)>,
+    /// Stores the clients coarse IP and parsed user agent within the Sessions metadata.
+    pub(crate) client_metadata: bool,
+    /// Clears the anonymous Session data when `Session::login` is called.
+    pub(crate) clear_on_login: bool,
+    /// The level routine messages like saves and sweeps are logged at.
+    pub(crate) log_level: Level,
     /// where All Database Storage options exist.
     pub(crate) database: DatabaseConfig,
     /// where All In Memory Storage options exist.
@@ -236,7 +419,13 @@ impl Debug for SessionConfig {
             .field("session_mode", &self.session_mode)
             .field("lifespan", &self.lifespan)
             .field("max_lifespan", &self.max_lifespan)
+            .field("absolute_lifespan", &self.absolute_lifespan)
             .field("clear_check_on_load", &self.clear_check_on_load)
+            .field("inline_maintenance", &self.inline_maintenance)
+            .field("max_sessions_per_owner", &self.max_sessions_per_owner)
+            .field("client_metadata", &self.client_metadata)
+            .field("clear_on_login", &self.clear_on_login)
+            .field("log_level", &self.log_level)
             .finish()
     }
 }
@@ -428,6 +617,22 @@ impl SessionConfig {
         self
     }
 
+    /// Set's how the Session ID is sent between the client and server.
+    /// Default is `SessionTransport::Cookie`. This can also be set per layer with `SessionLayer::with_transport`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{SessionConfig, SessionTransport};
+    ///
+    /// let config = SessionConfig::default().with_transport(SessionTransport::CookieThenHeader);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_transport(mut self, transport: SessionTransport) -> Self {
+        self.cookie_and_header.transport = transport;
+        self
+    }
+
     /// Set's the session's long term lifetime (expiration time) within database storage.
     ///
     /// # Examples
@@ -444,6 +649,25 @@ impl SessionConfig {
         self
     }
 
+    /// Set's the longest a Session can exist from when it was created, no matter how active it is.
+    /// The lifetimes above slide forward on every saved request while this one never does.
+    /// Once reached the Session is destroyed when it is next loaded, `SessionListener::on_expire`
+    /// is called and the request continues with a new Session. Renewing keeps the original creation time.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::SessionConfig;
+    /// use chrono::Duration;
+    ///
+    /// let config = SessionConfig::default().with_absolute_lifetime(Duration::hours(12));
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_absolute_lifetime(mut self, time: Duration) -> Self {
+        self.absolute_lifespan = Some(time);
+        self
+    }
+
     /// Set's the session's lifetime (expiration time) within memory storage.
     /// This setting should be Less than lifespan and max_lifespan. This is to
     /// Unload the data from memory and allow it to stay stored in the database.
@@ -465,6 +689,26 @@ impl SessionConfig {
         self
     }
 
+    /// Set's the most Sessions kept within memory. Once it is reached the least recently used Sessions
+    /// are evicted, along with an extra sixteenth of the capacity so this does not run on every request.
+    /// Sessions with requests still in flight are never evicted.
+    ///
+    /// Evicted Sessions are loaded from the database again on their next request. Sessions are
+    /// never evicted without a database, with the `SessionNullPool` or with the `SessionCookiePool`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{MemoryCapacity, SessionConfig};
+    ///
+    /// let config = SessionConfig::default().with_memory_capacity(MemoryCapacity::Bytes(64 * 1024 * 1024));
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_memory_capacity(mut self, capacity: MemoryCapacity) -> Self {
+        self.memory.capacity = Some(capacity);
+        self
+    }
+
     /// This value represents the offset duration for how often session purge for memory is ran.
     ///
     /// # Examples
@@ -516,6 +760,60 @@ impl SessionConfig {
         self
     }
 
+    /// Set's how the session handles being changed within the database by another
+    /// request or server since it was loaded. Default is `ConflictPolicy::LastWriteWins`.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{ConflictPolicy, SessionConfig};
+    ///
+    /// let config = SessionConfig::default().with_conflict_policy(ConflictPolicy::Error);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.database.conflict_policy = policy;
+        self
+    }
+
+    /// Set's what happens when the database can not be reached. Default is `DatabaseFailurePolicy::Fail`.
+    /// Use `SessionStore::is_degraded` to check if Sessions are currently only served from memory.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{DatabaseFailurePolicy, SessionConfig};
+    /// use chrono::Duration;
+    ///
+    /// let config = SessionConfig::default()
+    ///     .with_failure_policy(DatabaseFailurePolicy::ServeFromMemory(Duration::seconds(5)));
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_failure_policy(mut self, policy: DatabaseFailurePolicy) -> Self {
+        self.database.failure_policy = policy;
+        self
+    }
+
+    /// Set's how often the database expiry of a Session that did not change is extended.
+    /// Unchanged Sessions only have their expiry updated using `DatabasePool::touch`, and only
+    /// once the interval has passed since it was last extended. Changed Sessions are still fully stored,
+    /// and so are Sessions with an owner once the interval passed to keep their `last_seen` current.
+    /// Default is None which fully stores every unexpired Session on each request.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::SessionConfig;
+    /// use chrono::Duration;
+    ///
+    /// let config = SessionConfig::default().with_touch_interval(Duration::minutes(5));
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_touch_interval(mut self, interval: Duration) -> Self {
+        self.database.touch_interval = Some(interval);
+        self
+    }
+
     /// Set's the session's secure flag for if it gets sent over https.
     ///
     /// # Examples
@@ -552,7 +850,7 @@ impl SessionConfig {
     /// If you use Key::generate() it will make a new key each server reboot.
     /// To prevent this make and save a key to a config file for long term usage.
     /// For Extra Security Regenerate the key every so many months to a year.
-    /// A new key will invalidate all old Sessions so it be wise to run session_store.clear_store() on reboot.
+    /// A new key will invalidate all old Sessions unless the old key is added using `with_retired_key`.
     ///
     /// Must be Set to Some() in order to use Security::PerSession.
     ///
@@ -569,6 +867,28 @@ impl SessionConfig {
         self
     }
 
+    /// Adds a retired cookie and header signing key.
+    ///
+    /// Retired keys are only used to verify cookies and headers that were signed before
+    /// the key given to `with_key` was changed. The Session's cookie or header is signed
+    /// again with the active key on the next response, so users stay logged in while
+    /// the key is rotated. Remove the retired key once all sessions signed by it expired.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{Key, SessionConfig};
+    ///
+    /// let config = SessionConfig::default()
+    ///     .with_key(Key::generate())
+    ///     .with_retired_key(Key::generate());
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_retired_key(mut self, key: Key) -> Self {
+        self.cookie_and_header.retired_keys.push(key);
+        self
+    }
+
     /// Set's the session's database encyption key for per session key storage.
     ///
     /// Must be Set to Some() in order to use Security::PerSession or will panic if not.
@@ -586,6 +906,83 @@ impl SessionConfig {
         self
     }
 
+    /// Adds a retired database encyption key. Session data encrypted by a retired key
+    /// can still be loaded and will be encrypted with the current database key when it is stored again.
+    ///
+    /// Use this when rotating the database key by setting the new key with `with_database_key`
+    /// and adding the old key here. `SessionStore::reencrypt_all` can be used to rewrite
+    /// every session before the retired key is removed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{Key, SessionConfig};
+    ///
+    /// let config = SessionConfig::default()
+    ///     .with_database_key(Key::generate())
+    ///     .with_retired_database_key(Key::generate());
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_retired_database_key(mut self, key: Key) -> Self {
+        self.database.retired_database_keys.push(key);
+        self
+    }
+
+    /// Set's the codec used to Serialize the Session before it is stored within the database.
+    /// Default is `JsonCodec`.
+    ///
+    /// Sessions stored with a different codec can still be loaded and will be stored
+    /// with this codec the next time they are saved. Only the `JsonCodec` supports partial writes.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{JsonCodec, SessionConfig};
+    ///
+    /// let config = SessionConfig::default().with_codec(JsonCodec);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_codec(mut self, codec: impl SessionCodec) -> Self {
+        self.database.codec = Arc::new(codec);
+        self
+    }
+
+    /// Set's the compression used on Sessions before they are encrypted and stored within the database.
+    /// Default is `SessionCompression::None`.
+    ///
+    /// Sessions stored without compression can still be loaded. Partial writes are disabled
+    /// while compression is set since compressed Sessions can not be edited in place.
+    /// Sessions stored within the cookies by `SessionCookiePool` are never compressed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{SessionCompression, SessionConfig};
+    ///
+    /// let config = SessionConfig::default().with_compression(SessionCompression::None);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_compression(mut self, compression: SessionCompression) -> Self {
+        self.database.compression = compression;
+        self
+    }
+
+    /// Set's the size in bytes a serialized Session must reach before it is compressed.
+    /// Default is 1024.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::SessionConfig;
+    ///
+    /// let config = SessionConfig::default().with_compression_threshold(4096);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_compression_threshold(mut self, threshold: usize) -> Self {
+        self.database.compression_threshold = threshold;
+        self
+    }
+
     /// Set's the session's filters expected elements.
     /// Please Set this by a daily value.
     /// Example: 1000 * 60(secs) * 60(mins) * 24(hours) to get 1 days worth of visitors.
@@ -674,6 +1071,96 @@ impl SessionConfig {
         self
     }
 
+    /// Set's if the memory and database sweeps run during requests.
+    /// Set this to false when `SessionStore::spawn_maintenance` is used so requests
+    /// never pay for a full sweep.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::SessionConfig;
+    ///
+    /// let config = SessionConfig::default().with_inline_maintenance(false);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_inline_maintenance(mut self, enable: bool) -> Self {
+        self.inline_maintenance = enable;
+        self
+    }
+
+    /// Set's the most Sessions an owner can be signed into at once. It is checked whenever a request
+    /// gives a Session an owner using `Session::login` or `Session::set_owner`, and the owner's
+    /// other Sessions are found through the database's owner index so it works across servers.
+    /// Sessions stored only within memory or the cookies are only counted on the server that holds them.
+    ///
+    /// With `OwnerLimitPolicy::RefuseNew` the `SessionLayer`'s error handler builds the response,
+    /// which is a 403 by default, even if `SessionLayer::with_keep_response_on_error` is set.
+    /// Two logins racing on different servers can both get through the check.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{OwnerLimitPolicy, SessionConfig};
+    ///
+    /// let config = SessionConfig::default().with_max_sessions_per_owner(5, OwnerLimitPolicy::RefuseNew);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_max_sessions_per_owner(mut self, max: usize, policy: OwnerLimitPolicy) -> Self {
+        self.max_sessions_per_owner = Some((max.max(1), policy));
+        self
+    }
+
+    /// Set's if the clients IP and user agent are kept within the Sessions metadata. Default is true.
+    /// Only the network part of the IP and the browser and operating system names are kept.
+    /// Disable this for privacy sensitive deployments, which also removes them from existing
+    /// Sessions the next time they are used. `created_at` and `last_seen` are always kept.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::SessionConfig;
+    ///
+    /// let config = SessionConfig::default().with_client_metadata(false);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_client_metadata(mut self, enable: bool) -> Self {
+        self.client_metadata = enable;
+        self
+    }
+
+    /// Set's if `Session::login` clears the data set before the user logged in.
+    /// Enable this when anonymous data, like a half filled form, should not carry over into the
+    /// authenticated Session. Flash messages are cleared as well. Default is false.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::SessionConfig;
+    ///
+    /// let config = SessionConfig::default().with_clear_on_login(true);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_clear_on_login(mut self, enable: bool) -> Self {
+        self.clear_on_login = enable;
+        self
+    }
+
+    /// Set's the level that routine messages, like Sessions being saved or the sweeps running, are logged at.
+    /// Errors and warnings keep their own levels. Session IDs are never logged, only a short hash of them.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::SessionConfig;
+    ///
+    /// let config = SessionConfig::default().with_log_level(tracing::Level::DEBUG);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_log_level(mut self, level: Level) -> Self {
+        self.log_level = level;
+        self
+    }
+
     /// Set's the session's prefix_with_host to either true: __Host- gets prefixed to the cookie names false: __Host- does not get prepended.
     ///
     /// __Host- prefix: Cookies with names starting with __Host- must be set with the secure flag, must be from a secure page (HTTPS),
@@ -711,6 +1198,27 @@ impl SessionConfig {
         self
     }
 
+    /// Set's the Session ID header to be read from and sent as `Authorization: Bearer <token>`
+    /// instead of a header named after the session name. Only used when the transport uses headers.
+    ///
+    /// The token is still signed using the ip and user agent if a Key is set.
+    /// `SessionToken` can be returned from a handler to send the token within the response body.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{SessionConfig, SessionTransport};
+    ///
+    /// let config = SessionConfig::default()
+    ///     .with_transport(SessionTransport::Header)
+    ///     .with_bearer_token(true);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_bearer_token(mut self, enable: bool) -> Self {
+        self.cookie_and_header.bearer_token = enable;
+        self
+    }
+
     /// Set's the session's to use the Socket connected IP when hashing and verifying the cookies integrity.
     /// Only if with_ip_and_user_agent is also enabled.
     ///
@@ -803,8 +1311,16 @@ impl Default for SessionConfig {
             memory: MemoryConfig::default(),
             // Unload long term session after 60 days if it has not been accessed.
             max_lifespan: Duration::try_days(60).unwrap_or_default(),
+            // Sessions can be kept alive forever by using them unless a cap is set.
+            absolute_lifespan: None,
             session_mode: SessionMode::Persistent,
             clear_check_on_load: true,
+            // Sweep during requests unless a maintenance task is spawned.
+            inline_maintenance: true,
+            max_sessions_per_owner: None,
+            client_metadata: true,
+            clear_on_login: false,
+            log_level: Level::INFO,
             ip_user_agent: IpUserAgentConfig::default(),
         }
     }
@@ -824,6 +1340,8 @@ impl Default for MemoryConfig {
             filter_false_positive_probability: 0.01,
             // Always set to on.
             use_bloom_filters: true,
+            // Only the memory sweep unloads Sessions unless a capacity is set.
+            capacity: None,
         }
     }
 }
@@ -836,8 +1354,21 @@ impl Default for DatabaseConfig {
             // Default to purge old sessions in the database every 5 hours per request.
             purge_database_update: Duration::try_hours(5).unwrap_or_default(),
             always_save: false,
+            // Keep the old behaviour of overwriting whatever is stored.
+            conflict_policy: ConflictPolicy::LastWriteWins,
+            // Database errors fail the request unless degraded mode is opted into.
+            failure_policy: DatabaseFailurePolicy::Fail,
+            // Unchanged sessions keep being fully stored unless an interval is set.
+            touch_interval: None,
+            // JSON so Sessions stored by older versions can still be loaded.
+            codec: Arc::new(JsonCodec),
+            // Compression is opt in as it needs a feature enabled.
+            compression: SessionCompression::None,
+            compression_threshold: 1024,
             // Database key is set to None it will panic if you attempt to use SecurityMode::PerSession.
             database_key: None,
+            // No retired keys so only the database key is used to decrypt.
+            retired_database_keys: Vec::new(),
         }
     }
 }
@@ -855,8 +1386,11 @@ impl Default for CookieAndHeaderConfig {
             store_name: "store".into(),
             // Key is set to None so Private cookies are not used by default. Please set this if you want to use private cookies.
             key: None,
+            retired_keys: Vec::new(),
             prefix_with_host: false,
             with_ip_and_user_agent: true,
+            transport: SessionTransport::Cookie,
+            bearer_token: false,
         }
     }
 }
@@ -0,0 +1,298 @@
+use crate::{logging::LogId, sec::csrf, DatabasePool, Session};
+use axum::{
+    body::Body,
+    extract::FromRequestParts,
+    response::{IntoResponse, Response},
+};
+use futures::future::BoxFuture;
+use http::{header::CONTENT_TYPE, request::Parts, Method, Request, StatusCode};
+use std::{
+    borrow::Cow,
+    convert::Infallible,
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    task::{Context, Poll},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// The largest form body that will be read when looking for the CSRF form field.
+const FORM_LIMIT: usize = 2 * 1024 * 1024;
+
+/// CSRF Layer used with Axum to validate a per Session CSRF token on unsafe methods.
+///
+/// Must be added before the `SessionLayer` so it runs inside of it.
+/// POST, PUT, PATCH and DELETE requests are rejected with 403 Forbidden unless they
+/// contain a valid token within the header or the url encoded form field.
+///
+/// # Examples
+/// ```rust ignore
+/// use axum_session::{SessionNullPool, SessionConfig, SessionStore, SessionLayer, CsrfLayer};
+///
+/// let config = SessionConfig::default();
+/// let session_store = SessionStore::<SessionNullPool>::new(None, config).await.unwrap();
+/// let app = Router::new()
+///     .route("/", post(handler))
+///     .layer(CsrfLayer::<SessionNullPool>::new())
+///     .layer(SessionLayer::new(session_store));
+/// ```
+///
+pub struct CsrfLayer<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    /// Header the token is read from.
+    header_name: Cow<'static, str>,
+    /// Form field the token is read from if the header is not set.
+    form_field: Cow<'static, str>,
+    pool: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for CsrfLayer<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            header_name: self.header_name.clone(),
+            form_field: self.form_field.clone(),
+            pool: PhantomData,
+        }
+    }
+}
+
+impl<T> Debug for CsrfLayer<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsrfLayer")
+            .field("header_name", &self.header_name)
+            .field("form_field", &self.form_field)
+            .finish()
+    }
+}
+
+impl<T> Default for CsrfLayer<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    fn default() -> Self {
+        Self {
+            header_name: "x-csrf-token".into(),
+            form_field: "csrf_token".into(),
+            pool: PhantomData,
+        }
+    }
+}
+
+impl<T> CsrfLayer<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    /// Constructs a CsrfLayer using the `x-csrf-token` header and `csrf_token` form field.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{CsrfLayer, SessionNullPool};
+    ///
+    /// let layer = CsrfLayer::<SessionNullPool>::new();
+    /// ```
+    ///
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set's the header name the CSRF token is read from.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{CsrfLayer, SessionNullPool};
+    ///
+    /// let layer = CsrfLayer::<SessionNullPool>::new().with_header_name("x-xsrf-token");
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_header_name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.header_name = name.into();
+        self
+    }
+
+    /// Set's the url encoded form field the CSRF token is read from when the header is missing.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::{CsrfLayer, SessionNullPool};
+    ///
+    /// let layer = CsrfLayer::<SessionNullPool>::new().with_form_field("_csrf");
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_form_field(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.form_field = name.into();
+        self
+    }
+}
+
+impl<S, T> Layer<S> for CsrfLayer<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    type Service = CsrfService<S, T>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CsrfService {
+            layer: self.clone(),
+            inner,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CsrfService<S, T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    pub(crate) layer: CsrfLayer<T>,
+    pub(crate) inner: S,
+}
+
+impl<S, T> Debug for CsrfService<S, T>
+where
+    S: Debug,
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsrfService")
+            .field("layer", &self.layer)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<S, T> Service<Request<Body>> for CsrfService<S, T>
+where
+    S: Service<Request<Body>, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let layer = self.layer.clone();
+        let not_ready_inner = self.inner.clone();
+        let mut ready_inner = std::mem::replace(&mut self.inner, not_ready_inner);
+
+        Box::pin(async move {
+            let Some(session) = req.extensions().get::<Session<T>>().cloned() else {
+                tracing::error!("Can't find Axum `Session` for CSRF. Is `SessionLayer` enabled?");
+                return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            };
+
+            let token = session.store.csrf_token(session.id.clone());
+            let (mut parts, body) = req.into_parts();
+
+            let body = if is_unsafe(&parts.method) {
+                let (sent, body) = match parts
+                    .headers
+                    .get(layer.header_name.as_ref())
+                    .and_then(|h| h.to_str().ok())
+                {
+                    Some(header) => (Some(header.to_owned()), body),
+                    None if is_form(&parts) => {
+                        let Ok(bytes) = axum::body::to_bytes(body, FORM_LIMIT).await else {
+                            return Ok(StatusCode::PAYLOAD_TOO_LARGE.into_response());
+                        };
+
+                        let sent = form_urlencoded::parse(&bytes)
+                            .find(|(name, _)| name == layer.form_field.as_ref())
+                            .map(|(_, value)| value.into_owned());
+
+                        (sent, Body::from(bytes))
+                    }
+                    None => (None, body),
+                };
+
+                if !sent.is_some_and(|sent| csrf::verify(&token, &sent)) {
+                    tracing::warn!(session = %LogId(&session.id), "CSRF token was invalid.");
+                    return Ok((StatusCode::FORBIDDEN, "Invalid CSRF token").into_response());
+                }
+
+                body
+            } else {
+                body
+            };
+
+            parts.extensions.insert(CsrfToken(csrf::mask(&token)));
+            ready_inner.call(Request::from_parts(parts, body)).await
+        })
+    }
+}
+
+/// Methods that change state and need a valid CSRF token.
+fn is_unsafe(method: &Method) -> bool {
+    !matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    )
+}
+
+fn is_form(parts: &Parts) -> bool {
+    parts
+        .headers
+        .get(CONTENT_TYPE)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.starts_with("application/x-www-form-urlencoded"))
+}
+
+/// The masked CSRF token for the current request to be placed within forms or headers.
+/// The mask changes every request but the token it hides only changes when the Session is renewed.
+///
+/// If the Session is renewed within the handler use `Session::csrf_token` to get the new token.
+///
+/// # Examples
+/// ```rust ignore
+/// async fn form(token: CsrfToken) -> Html<String> {
+///     Html(format!(r#"<input type="hidden" name="csrf_token" value="{token}">"#))
+/// }
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsrfToken(pub String);
+
+impl CsrfToken {
+    /// Returns the masked token.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CsrfToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Adds `FromRequestParts<B>` for CsrfToken
+///
+/// Returns the CsrfToken from Axum's request extensions state
+impl<S> FromRequestParts<S> for CsrfToken
+where
+    S: Send + Sync,
+{
+    type Rejection = (http::StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<CsrfToken>().cloned().ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Can't extract Axum `CsrfToken`. Is `CsrfLayer` enabled?",
+        ))
+    }
+}
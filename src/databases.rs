@@ -4,5 +4,11 @@ pub use self::any_db::*;
 mod null;
 pub use null::*;
 
+mod cookie;
+pub use cookie::*;
+
 mod database;
-pub use database::{DatabaseError, DatabasePool};
+pub use database::{DatabaseError, DatabasePool, SessionPatch};
+
+#[cfg(test)]
+pub(crate) mod mock;
@@ -1,4 +1,4 @@
-use crate::{DatabaseError, DatabasePool, Session, SessionStore};
+use crate::{DatabaseError, DatabasePool, Session, SessionPatch, SessionStore};
 use async_trait::async_trait;
 use std::fmt::Debug;
 use std::sync::Arc;
@@ -53,6 +53,56 @@ impl DatabasePool for SessionAnyPool {
         self.pool.store(id, session, expires, table_name).await
     }
 
+    async fn compare_and_store(
+        &self,
+        id: &str,
+        session: &str,
+        expires: i64,
+        version: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        self.pool
+            .compare_and_store(id, session, expires, version, table_name)
+            .await
+    }
+
+    async fn set_owner(
+        &self,
+        id: &str,
+        owner: Option<&str>,
+        table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        self.pool.set_owner(id, owner, table_name).await
+    }
+
+    async fn get_ids_by_owner(
+        &self,
+        owner: &str,
+        table_name: &str,
+    ) -> Result<Vec<String>, DatabaseError> {
+        self.pool.get_ids_by_owner(owner, table_name).await
+    }
+
+    async fn store_partial(
+        &self,
+        id: &str,
+        patch: &SessionPatch,
+        expires: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        self.pool
+            .store_partial(id, patch, expires, table_name)
+            .await
+    }
+
+    async fn touch(&self, id: &str, expires: i64, table_name: &str) -> Result<bool, DatabaseError> {
+        self.pool.touch(id, expires, table_name).await
+    }
+
+    async fn load_expiry(&self, id: &str, table_name: &str) -> Result<Option<i64>, DatabaseError> {
+        self.pool.load_expiry(id, table_name).await
+    }
+
     async fn load(&self, id: &str, table_name: &str) -> Result<Option<String>, DatabaseError> {
         self.pool.load(id, table_name).await
     }
@@ -80,4 +130,12 @@ impl DatabasePool for SessionAnyPool {
     fn auto_handles_expiry(&self) -> bool {
         self.pool.auto_handles_expiry()
     }
+
+    fn stores_in_cookie(&self) -> bool {
+        self.pool.stores_in_cookie()
+    }
+
+    fn stores_nothing(&self) -> bool {
+        self.pool.stores_nothing()
+    }
 }
@@ -0,0 +1,85 @@
+use crate::{DatabaseError, DatabasePool, Session, SessionStore};
+use async_trait::async_trait;
+
+///Cookie's Session Helper type for a Session stored within the cookies.
+pub type SessionCookieSession = Session<SessionCookiePool>;
+///Cookie's Session Store Helper type for a Session stored within the cookies.
+pub type SessionCookieSessionStore = SessionStore<SessionCookiePool>;
+
+/// Cookie Pool type for a stateless DatabaseLess Session.
+/// The whole Session is encrypted with the `SessionConfig` key and stored within the cookies
+/// instead of only its ID. Large Sessions are split across two numbered cookies of up to 3072
+/// bytes each, so Sessions larger than 6144 bytes once encrypted are refused with an error to
+/// stay under proxy header limits. This limit can not be changed.
+///
+/// Sessions stored within the cookies are never compressed, even if `SessionConfig::with_compression`
+/// is set, since compressing secrets next to data the client controls leaks them through the size.
+///
+/// A key must be set with `SessionConfig::with_key` and the transport must use cookies.
+///
+/// # Examples
+/// ```rust ignore
+/// use axum_session::{Key, SessionConfig, SessionCookiePool, SessionStore};
+///
+/// let config = SessionConfig::default().with_key(Key::generate());
+/// let session_store = SessionStore::<SessionCookiePool>::new(Some(SessionCookiePool), config)
+///     .await
+///     .unwrap();
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct SessionCookiePool;
+
+#[async_trait]
+impl DatabasePool for SessionCookiePool {
+    async fn initiate(&self, _table_name: &str) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    async fn delete_by_expiry(&self, _table_name: &str) -> Result<Vec<String>, DatabaseError> {
+        Ok(Vec::new())
+    }
+
+    async fn count(&self, _table_name: &str) -> Result<i64, DatabaseError> {
+        return Ok(0);
+    }
+
+    async fn store(
+        &self,
+        _id: &str,
+        _session: &str,
+        _expires: i64,
+        _table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    async fn load(&self, _id: &str, _table_name: &str) -> Result<Option<String>, DatabaseError> {
+        Ok(None)
+    }
+
+    async fn delete_one_by_id(&self, _id: &str, _table_name: &str) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    async fn exists(&self, _id: &str, _table_name: &str) -> Result<bool, DatabaseError> {
+        Ok(false)
+    }
+
+    async fn delete_all(&self, _table_name: &str) -> Result<(), DatabaseError> {
+        Ok(())
+    }
+
+    async fn get_ids(&self, _table_name: &str) -> Result<Vec<String>, DatabaseError> {
+        Ok(Vec::new())
+    }
+
+    fn auto_handles_expiry(&self) -> bool {
+        // The expiration is checked when the cookies are loaded.
+        true
+    }
+
+    fn stores_in_cookie(&self) -> bool {
+        true
+    }
+}
@@ -20,6 +20,8 @@ pub trait DatabasePool {
     /// if an error occurs it should be propagated to the caller.
     /// expires is a unix timestamp(number of non-leap seconds since January 1, 1970 0:00:00 UTC)
     /// which is set to UTC::now() + the expiration time.
+    /// Databases that implement `compare_and_store` should increase the stored version by one,
+    /// starting at 1, so other servers using a versioned `ConflictPolicy` see the change.
     async fn store(
         &self,
         id: &str,
@@ -28,6 +30,72 @@ pub trait DatabasePool {
         table_name: &str,
     ) -> Result<(), DatabaseError>;
 
+    /// This is called to store a session in the database only if the version currently stored
+    /// for the id matches the given version. The stored version is then set to `version + 1`.
+    /// A version of 0 means the session was never stored and should be inserted if no row exists.
+    /// Returns false without storing anything if the versions did not match.
+    /// if an error occurs it should be propagated to the caller.
+    ///
+    /// The default implementation falls back to `store` and always returns true,
+    /// which makes the last write win for databases that do not support this.
+    async fn compare_and_store(
+        &self,
+        id: &str,
+        session: &str,
+        expires: i64,
+        version: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let _ = version;
+        self.store(id, session, expires, table_name).await?;
+        Ok(true)
+    }
+
+    /// This is called to store only the data keys that changed since the session was last stored.
+    /// expires should be updated the same way as within `store`.
+    /// Returns false if nothing was stored, like when the session does not exist yet or the
+    /// database does not support this, so the whole session will be stored with `store` instead.
+    /// The `fields` should be stored along with the data and the stored version increased by one,
+    /// so other servers using a versioned `ConflictPolicy` see the change.
+    /// Databases that can only replace the whole value, like Redis, can keep the patch within a
+    /// hash next to the session using `SessionPatch::hash_fields` and apply it again when loading
+    /// with `SessionPatch::apply_hash_fields`.
+    /// if an error occurs it should be propagated to the caller.
+    ///
+    /// The default implementation always returns false.
+    async fn store_partial(
+        &self,
+        id: &str,
+        patch: &SessionPatch,
+        expires: i64,
+        table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let _ = (id, patch, expires, table_name);
+        Ok(false)
+    }
+
+    /// This is called to only extend when a session expires without storing the session again.
+    /// It should only update the expiry column or TTL, using expires the same way as within `store`.
+    /// Returns false if nothing was updated, like when the session does not exist or the
+    /// database does not support this, so the whole session will be stored with `store` instead.
+    /// if an error occurs it should be propagated to the caller.
+    ///
+    /// The default implementation always returns false.
+    async fn touch(&self, id: &str, expires: i64, table_name: &str) -> Result<bool, DatabaseError> {
+        let _ = (id, expires, table_name);
+        Ok(false)
+    }
+
+    /// This is called to receive when a session expires, as the unix timestamp given to `store` or `touch`.
+    /// Returns None if the session does not exist, never expires or the database can not tell.
+    /// if an error occurs it should be propagated to the caller.
+    ///
+    /// The default implementation always returns None.
+    async fn load_expiry(&self, id: &str, table_name: &str) -> Result<Option<i64>, DatabaseError> {
+        let _ = (id, table_name);
+        Ok(None)
+    }
+
     /// This is called to receive the session from the database using the given table name.
     /// if an error occurs it should be propagated to the caller.
     async fn load(&self, id: &str, table_name: &str) -> Result<Option<String>, DatabaseError>;
@@ -52,7 +120,147 @@ pub trait DatabasePool {
     /// if an error occurs it should be propagated to the caller.
     async fn get_ids(&self, table_name: &str) -> Result<Vec<String>, DatabaseError>;
 
+    /// This is called to set or clear the owner of a session using the given table name.
+    /// The owner should be stored so it can be looked up with `get_ids_by_owner`.
+    /// This is only called after the session itself has been stored.
+    /// if an error occurs it should be propagated to the caller.
+    ///
+    /// The default implementation does nothing, which means sessions will not be found by owner.
+    async fn set_owner(
+        &self,
+        id: &str,
+        owner: Option<&str>,
+        table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        let _ = (id, owner, table_name);
+        Ok(())
+    }
+
+    /// This is called to get all id's owned by the given owner using the given table name.
+    /// Returning id's that are no longer owned by the owner is allowed as they get checked after loading.
+    /// if an error occurs it should be propagated to the caller.
+    ///
+    /// The default implementation returns no id's.
+    async fn get_ids_by_owner(
+        &self,
+        owner: &str,
+        table_name: &str,
+    ) -> Result<Vec<String>, DatabaseError> {
+        let _ = (owner, table_name);
+        Ok(Vec::new())
+    }
+
     fn auto_handles_expiry(&self) -> bool;
+
+    /// Returns true if the whole session is stored within the cookies instead of this database.
+    /// Only the `SessionCookiePool` should return true.
+    fn stores_in_cookie(&self) -> bool {
+        false
+    }
+
+    /// Returns true if sessions are never stored, so they only live within memory.
+    /// Only the `SessionNullPool` should return true.
+    fn stores_nothing(&self) -> bool {
+        false
+    }
+}
+
+/// The changes made to a session's data since it was last stored.
+/// Used by `DatabasePool::store_partial` to only write the keys that changed.
+#[derive(Debug, Clone, Default)]
+pub struct SessionPatch {
+    /// JSON object of the changed keys and their stored values.
+    pub changed: String,
+    /// The keys that were removed.
+    pub removed: Vec<String>,
+    /// JSON object of the sessions other fields that change on every request, like `last_seen`.
+    pub fields: String,
+    /// JSON merge patch (RFC 7396) that applies the changes and fields to the whole stored session.
+    pub merge_patch: String,
+}
+
+impl SessionPatch {
+    /// The hash fields to set and the hash fields to delete for databases that keep
+    /// the changes within a hash next to the stored session, like Redis.
+    /// Changed keys are set as `set:<key>` and removed keys as `del:<key>`, each deleting
+    /// the other, and the `fields` are set as `fields`.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let (set, del) = patch.hash_fields();
+    /// ```
+    ///
+    pub fn hash_fields(&self) -> (Vec<(String, String)>, Vec<String>) {
+        let changed: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&self.changed).unwrap_or_default();
+        let mut set = Vec::with_capacity(changed.len() + self.removed.len() + 1);
+        let mut del = Vec::with_capacity(changed.len() + self.removed.len());
+
+        for (key, value) in changed {
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                value => value.to_string(),
+            };
+
+            del.push(format!("del:{key}"));
+            set.push((format!("set:{key}"), value));
+        }
+
+        for key in &self.removed {
+            del.push(format!("set:{key}"));
+            set.push((format!("del:{key}"), String::new()));
+        }
+
+        set.push(("fields".to_owned(), self.fields.clone()));
+        (set, del)
+    }
+
+    /// Applies the hash fields written from `hash_fields` onto the stored session.
+    /// A `version` field replaces the sessions version.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let session = SessionPatch::apply_hash_fields(&session, fields)?;
+    /// ```
+    ///
+    pub fn apply_hash_fields(
+        session: &str,
+        fields: Vec<(String, String)>,
+    ) -> Result<String, DatabaseError> {
+        let error = |err: serde_json::Error| DatabaseError::GenericSelectError(err.to_string());
+        let mut session: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(session).map_err(error)?;
+
+        for (field, value) in fields {
+            if let Some(key) = field.strip_prefix("set:") {
+                if let Some(data) = session
+                    .entry("data")
+                    .or_insert_with(|| serde_json::Value::Object(Default::default()))
+                    .as_object_mut()
+                {
+                    data.insert(key.to_owned(), serde_json::Value::String(value));
+                }
+            } else if let Some(key) = field.strip_prefix("del:") {
+                if let Some(data) = session
+                    .get_mut("data")
+                    .and_then(|data| data.as_object_mut())
+                {
+                    data.remove(key);
+                }
+            } else if field == "fields" {
+                let values: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_str(&value).map_err(error)?;
+                session.extend(values);
+            } else if field == "version" {
+                let version: i64 = value.parse().map_err(|_| {
+                    DatabaseError::GenericSelectError(format!("invalid version {value}"))
+                })?;
+                session.insert(field, version.into());
+            }
+        }
+
+        Ok(serde_json::Value::Object(session).to_string())
+    }
 }
 
 #[derive(Error, Debug)]
@@ -0,0 +1,228 @@
+use crate::{DatabaseError, DatabasePool, SessionPatch};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// A row stored within the `MockPool`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MockRow {
+    pub(crate) session: String,
+    pub(crate) expires: i64,
+    pub(crate) version: i64,
+    pub(crate) owner: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct MockState {
+    pub(crate) rows: HashMap<String, MockRow>,
+    /// Every call that fails while this is set, like when the database is unreachable.
+    pub(crate) failing: bool,
+    /// If `touch` updates the expiry or returns false like the default implementation.
+    pub(crate) touches: bool,
+    /// The writes made to the pool in order, as `method:id`.
+    pub(crate) writes: Vec<String>,
+}
+
+/// In memory `DatabasePool` used to test how the `SessionStore` and `SessionLayer` use a database.
+/// Clones share the same rows.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MockPool {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockPool {
+    pub(crate) fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().expect("mock pool lock was poisoned")
+    }
+
+    pub(crate) fn row(&self, id: &str) -> Option<MockRow> {
+        self.state().rows.get(id).cloned()
+    }
+
+    pub(crate) fn set_failing(&self, failing: bool) {
+        self.state().failing = failing;
+    }
+
+    pub(crate) fn take_writes(&self) -> Vec<String> {
+        std::mem::take(&mut self.state().writes)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, MockState>, DatabaseError> {
+        let state = self.state();
+
+        if state.failing {
+            return Err(DatabaseError::GenericAcquire(
+                "mock database is unavailable".to_owned(),
+            ));
+        }
+
+        Ok(state)
+    }
+}
+
+#[async_trait]
+impl DatabasePool for MockPool {
+    async fn initiate(&self, _table_name: &str) -> Result<(), DatabaseError> {
+        self.lock().map(|_| ())
+    }
+
+    async fn count(&self, _table_name: &str) -> Result<i64, DatabaseError> {
+        Ok(self.lock()?.rows.len() as i64)
+    }
+
+    async fn store(
+        &self,
+        id: &str,
+        session: &str,
+        expires: i64,
+        _table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        let mut state = self.lock()?;
+        state.writes.push(format!("store:{id}"));
+        let row = state.rows.entry(id.to_owned()).or_default();
+        row.session = session.to_owned();
+        row.expires = expires;
+        row.version += 1;
+        Ok(())
+    }
+
+    async fn compare_and_store(
+        &self,
+        id: &str,
+        session: &str,
+        expires: i64,
+        version: i64,
+        _table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let mut state = self.lock()?;
+        let stored = state.rows.get(id).map_or(0, |row| row.version);
+
+        if stored != version {
+            return Ok(false);
+        }
+
+        state.writes.push(format!("compare_and_store:{id}"));
+        let row = state.rows.entry(id.to_owned()).or_default();
+        row.session = session.to_owned();
+        row.expires = expires;
+        row.version = version + 1;
+        Ok(true)
+    }
+
+    async fn store_partial(
+        &self,
+        id: &str,
+        _patch: &SessionPatch,
+        _expires: i64,
+        _table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        self.lock()?.writes.push(format!("store_partial:{id}"));
+        Ok(false)
+    }
+
+    async fn touch(
+        &self,
+        id: &str,
+        expires: i64,
+        _table_name: &str,
+    ) -> Result<bool, DatabaseError> {
+        let mut state = self.lock()?;
+
+        if !state.touches {
+            return Ok(false);
+        }
+
+        state.writes.push(format!("touch:{id}"));
+
+        Ok(match state.rows.get_mut(id) {
+            Some(row) => {
+                row.expires = expires;
+                true
+            }
+            None => false,
+        })
+    }
+
+    async fn load_expiry(&self, id: &str, _table_name: &str) -> Result<Option<i64>, DatabaseError> {
+        Ok(self.lock()?.rows.get(id).map(|row| row.expires))
+    }
+
+    async fn load(&self, id: &str, _table_name: &str) -> Result<Option<String>, DatabaseError> {
+        Ok(self
+            .lock()?
+            .rows
+            .get(id)
+            .filter(|row| row.expires > Utc::now().timestamp())
+            .map(|row| row.session.clone()))
+    }
+
+    async fn delete_one_by_id(&self, id: &str, _table_name: &str) -> Result<(), DatabaseError> {
+        let mut state = self.lock()?;
+        state.writes.push(format!("delete:{id}"));
+        state.rows.remove(id);
+        Ok(())
+    }
+
+    async fn exists(&self, id: &str, _table_name: &str) -> Result<bool, DatabaseError> {
+        Ok(self.lock()?.rows.contains_key(id))
+    }
+
+    async fn delete_by_expiry(&self, _table_name: &str) -> Result<Vec<String>, DatabaseError> {
+        let mut state = self.lock()?;
+        let now = Utc::now().timestamp();
+        let expired: Vec<String> = state
+            .rows
+            .iter()
+            .filter(|(_, row)| row.expires <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        expired.iter().for_each(|id| {
+            state.rows.remove(id);
+        });
+        Ok(expired)
+    }
+
+    async fn delete_all(&self, _table_name: &str) -> Result<(), DatabaseError> {
+        self.lock()?.rows.clear();
+        Ok(())
+    }
+
+    async fn get_ids(&self, _table_name: &str) -> Result<Vec<String>, DatabaseError> {
+        Ok(self.lock()?.rows.keys().cloned().collect())
+    }
+
+    async fn set_owner(
+        &self,
+        id: &str,
+        owner: Option<&str>,
+        _table_name: &str,
+    ) -> Result<(), DatabaseError> {
+        if let Some(row) = self.lock()?.rows.get_mut(id) {
+            row.owner = owner.map(str::to_owned);
+        }
+
+        Ok(())
+    }
+
+    async fn get_ids_by_owner(
+        &self,
+        owner: &str,
+        _table_name: &str,
+    ) -> Result<Vec<String>, DatabaseError> {
+        Ok(self
+            .lock()?
+            .rows
+            .iter()
+            .filter(|(_, row)| row.owner.as_deref() == Some(owner))
+            .map(|(id, _)| id.clone())
+            .collect())
+    }
+
+    fn auto_handles_expiry(&self) -> bool {
+        false
+    }
+}
@@ -58,4 +58,8 @@ impl DatabasePool for SessionNullPool {
     fn auto_handles_expiry(&self) -> bool {
         false
     }
+
+    fn stores_nothing(&self) -> bool {
+        true
+    }
 }
@@ -27,4 +27,8 @@ pub enum SessionError {
     Session will get removed on next Session request purge update if no changes are done."
     )]
     OldSessionError,
+    #[error("The Session was changed by another request before it could be saved.")]
+    VersionConflict,
+    #[error("The owner already has the most Sessions allowed.")]
+    SessionLimitReached,
 }
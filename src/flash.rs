@@ -0,0 +1,137 @@
+use crate::{DatabasePool, Session};
+use axum::extract::FromRequestParts;
+use http::request::Parts;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    marker::PhantomData,
+    ops::Deref,
+};
+
+/// The key the queued flash messages are stored under within the Session's HashMap.
+pub(crate) const FLASH_KEY: &str = "_axum_session_flashes";
+
+/// The Level of a Flash Message. Can be used to pick how the message is displayed.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::FlashLevel;
+///
+/// assert_eq!(FlashLevel::Warning.to_string(), "warning");
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlashLevel {
+    Debug,
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl FlashLevel {
+    /// Returns the lower case name of the Level.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FlashLevel::Debug => "debug",
+            FlashLevel::Info => "info",
+            FlashLevel::Success => "success",
+            FlashLevel::Warning => "warning",
+            FlashLevel::Error => "error",
+        }
+    }
+}
+
+impl Display for FlashLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A message queued with `Session::flash` to be shown once on a later request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlashMessage {
+    pub level: FlashLevel,
+    pub message: String,
+}
+
+/// Takes all the queued Flash Messages from the Session.
+/// Once extracted they are removed from the Session so they are only shown once.
+///
+/// # Examples
+/// ```rust ignore
+/// async fn handler(flashes: Flashes<SessionNullPool>) -> String {
+///     flashes.iter().map(|f| format!("{}: {}", f.level, f.message)).collect()
+/// }
+/// ```
+///
+pub struct Flashes<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    messages: Vec<FlashMessage>,
+    pool: PhantomData<fn() -> T>,
+}
+
+impl<T> Flashes<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    /// Returns the Flash Messages.
+    pub fn into_inner(self) -> Vec<FlashMessage> {
+        self.messages
+    }
+}
+
+impl<T> Debug for Flashes<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Flashes").field(&self.messages).finish()
+    }
+}
+
+impl<T> Deref for Flashes<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    type Target = [FlashMessage];
+
+    fn deref(&self) -> &Self::Target {
+        &self.messages
+    }
+}
+
+impl<T> IntoIterator for Flashes<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    type Item = FlashMessage;
+    type IntoIter = std::vec::IntoIter<FlashMessage>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.messages.into_iter()
+    }
+}
+
+/// Adds `FromRequestParts<B>` for Flashes
+///
+/// Drains the Flash Messages from the Session within Axum's request extensions state
+impl<T, S> FromRequestParts<S> for Flashes<T>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+    S: Send + Sync,
+{
+    type Rejection = (http::StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = Session::<T>::from_request_parts(parts, state).await?;
+
+        Ok(Flashes {
+            messages: session.take_flashes(),
+            pool: PhantomData,
+        })
+    }
+}
@@ -1,20 +1,15 @@
-#[cfg(not(feature = "rest_mode"))]
-use crate::CookiesAdditionJar;
-use crate::{DatabasePool, Session, SessionConfig, SessionStore};
-#[cfg(not(feature = "rest_mode"))]
+use crate::{
+    sec::{sign_header, verify_header_with_retired},
+    CookiesAdditionJar, DatabasePool, Session, SessionConfig, SessionData, SessionStore,
+    SessionTransport,
+};
 use cookie::{Cookie, CookieJar, Key};
 use forwarded_header_value::{ForwardedHeaderValue, Identifier};
-#[cfg(not(feature = "rest_mode"))]
-use http::header::{COOKIE, SET_COOKIE};
-#[cfg(feature = "rest_mode")]
-use http::{header::HeaderName, HeaderValue};
 use http::{
-    header::{FORWARDED, USER_AGENT},
+    header::{HeaderName, AUTHORIZATION, COOKIE, FORWARDED, SET_COOKIE, USER_AGENT},
     request::Request,
-    HeaderMap,
+    HeaderMap, HeaderValue,
 };
-#[cfg(feature = "rest_mode")]
-use std::collections::HashMap;
 use std::{
     fmt::Debug,
     net::{IpAddr, SocketAddr},
@@ -22,6 +17,14 @@ use std::{
 
 const X_REAL_IP: &str = "x-real-ip";
 const X_FORWARDED_FOR: &str = "x-forwarded-for";
+/// The largest value placed within a single Session cookie. Browsers limit
+/// a cookie to around 4096 bytes including its name and attributes.
+const COOKIE_CHUNK_SIZE: usize = 3072;
+/// The most cookies a Session stored within the cookies may be split across.
+/// Kept low as proxies and servers commonly limit the request headers to around 8KB.
+const MAX_COOKIE_CHUNKS: usize = 2;
+/// The largest Session value that can be stored within the cookies.
+pub(crate) const MAX_COOKIE_SESSION_SIZE: usize = COOKIE_CHUNK_SIZE * MAX_COOKIE_CHUNKS;
 
 enum NameType {
     Store,
@@ -30,42 +33,39 @@ enum NameType {
 
 impl NameType {
     #[inline]
-    pub(crate) fn get_name(&self, config: &SessionConfig) -> String {
+    pub(crate) fn get_name(&self, config: &SessionConfig, is_cookie: bool) -> String {
         let name = match self {
             NameType::Data => config.cookie_and_header.session_name.to_string(),
             NameType::Store => config.cookie_and_header.store_name.to_string(),
         };
 
-        #[cfg(not(feature = "rest_mode"))]
-        if config.cookie_and_header.prefix_with_host {
+        // Headers can not use the cookie prefixes.
+        if is_cookie && config.cookie_and_header.prefix_with_host {
             let mut prefixed = "__Host-".to_owned();
             prefixed.push_str(&name);
             prefixed
         } else {
             name
         }
-
-        #[allow(clippy::let_and_return)]
-        #[cfg(feature = "rest_mode")]
-        name
     }
 }
 
-#[cfg(not(feature = "rest_mode"))]
-pub async fn get_headers_and_key<T>(
+fn get_cookie_and_key<T>(
     store: &SessionStore<T>,
-    cookies: CookieJar,
+    cookies: &CookieJar,
     ip_user_agent: &str,
 ) -> (Option<String>, bool)
 where
     T: DatabasePool + Clone + Debug + Sync + Send + 'static,
 {
     let key = store.config.cookie_and_header.key.as_ref();
+    let retired_keys = &store.config.cookie_and_header.retired_keys;
 
     let value = cookies
         .get_cookie(
             &store.config.cookie_and_header.session_name,
             key,
+            retired_keys,
             ip_user_agent.to_owned(),
             false,
         )
@@ -75,6 +75,7 @@ where
         .get_cookie(
             &store.config.cookie_and_header.store_name,
             key,
+            retired_keys,
             ip_user_agent.to_owned(),
             true,
         )
@@ -83,33 +84,69 @@ where
     (value, storable)
 }
 
-#[cfg(feature = "rest_mode")]
-pub async fn get_headers_and_key<T>(
+fn cookie_chunk_name(config: &SessionConfig, index: usize) -> String {
+    format!("{}.{index}", NameType::Data.get_name(config, true))
+}
+
+/// Joins the numbered cookies of a Session stored within the cookies and loads it.
+/// Also returns how many cookies it was split across so unused ones can be removed.
+pub(crate) fn get_cookie_session<T>(
     store: &SessionStore<T>,
-    headers: HashMap<String, String>,
+    cookies: &CookieJar,
+) -> (Option<SessionData>, usize)
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    let mut value = String::new();
+    let mut chunks = 0;
+
+    while chunks < MAX_COOKIE_CHUNKS {
+        let Some(cookie) = cookies.get(&cookie_chunk_name(&store.config, chunks)) else {
+            break;
+        };
+
+        value.push_str(cookie.value());
+        chunks += 1;
+    }
+
+    if value.is_empty() {
+        return (None, chunks);
+    }
+
+    (store.deserialize_cookie_session(&value), chunks)
+}
+
+fn get_header_and_key<T>(
+    store: &SessionStore<T>,
+    headers: &HeaderMap,
     ip_user_agent: &str,
 ) -> (Option<String>, bool)
 where
     T: DatabasePool + Clone + Debug + Sync + Send + 'static,
 {
-    use crate::sec::verify_header;
     let key = store.config.cookie_and_header.key.as_ref();
+    let retired_keys = &store.config.cookie_and_header.retired_keys;
 
-    let name = store.config.cookie_and_header.session_name.to_string();
-    let value = headers.get(&name).and_then(|c| {
+    let name = store.config.cookie_and_header.session_name.as_ref();
+    let value = if store.config.cookie_and_header.bearer_token {
+        bearer_token(headers)
+    } else {
+        header_value(headers, name)
+    };
+
+    let value = value.and_then(|c| {
         if let Some(key) = key {
-            verify_header(c, key, ip_user_agent).ok()
+            verify_header_with_retired(c, key, retired_keys, ip_user_agent).ok()
         } else {
             Some(c.to_owned())
         }
     });
 
-    let name = store.config.cookie_and_header.store_name.to_string();
-    let storable = headers
-        .get(&name)
+    let name = store.config.cookie_and_header.store_name.as_ref();
+    let storable = header_value(headers, name)
         .and_then(|c| {
             if let Some(key) = key {
-                verify_header(c, key, ip_user_agent).ok()
+                verify_header_with_retired(c, key, retired_keys, ip_user_agent).ok()
             } else {
                 Some(c.to_owned())
             }
@@ -119,12 +156,12 @@ where
     (value, storable.unwrap_or(false))
 }
 
-#[cfg(not(feature = "rest_mode"))]
 pub(crate) trait CookiesExt {
     fn get_cookie(
         &self,
         name: &str,
         key: Option<&Key>,
+        retired_keys: &[Key],
         message: String,
         bypass: bool,
     ) -> Option<Cookie<'static>>;
@@ -137,18 +174,21 @@ pub(crate) trait CookiesExt {
     );
 }
 
-#[cfg(not(feature = "rest_mode"))]
 impl CookiesExt for CookieJar {
     fn get_cookie(
         &self,
         name: &str,
         key: Option<&Key>,
+        retired_keys: &[Key],
         message: String,
         bypass: bool,
     ) -> Option<Cookie<'static>> {
         if !bypass {
             if let Some(key) = key {
-                return self.message_signed(key, message).get(name);
+                return self
+                    .message_signed(key, message)
+                    .with_retired_keys(retired_keys)
+                    .get(name);
             }
         }
 
@@ -173,9 +213,8 @@ impl CookiesExt for CookieJar {
     }
 }
 
-#[cfg(not(feature = "rest_mode"))]
 fn create_cookie<'a>(config: &SessionConfig, value: String, cookie_type: NameType) -> Cookie<'a> {
-    let mut cookie_builder = Cookie::build((cookie_type.get_name(config), value))
+    let mut cookie_builder = Cookie::build((cookie_type.get_name(config, true), value))
         .path(config.cookie_and_header.cookie_path.clone())
         .secure(config.cookie_and_header.cookie_secure)
         .http_only(config.cookie_and_header.cookie_http_only)
@@ -194,9 +233,8 @@ fn create_cookie<'a>(config: &SessionConfig, value: String, cookie_type: NameTyp
     cookie_builder.build()
 }
 
-#[cfg(not(feature = "rest_mode"))]
 fn remove_cookie<'a>(config: &SessionConfig, cookie_type: NameType) -> Cookie<'a> {
-    let mut cookie_builder = Cookie::build((cookie_type.get_name(config), ""))
+    let mut cookie_builder = Cookie::build((cookie_type.get_name(config, true), ""))
         .path(config.cookie_and_header.cookie_path.clone())
         .http_only(config.cookie_and_header.cookie_http_only)
         .same_site(cookie::SameSite::None);
@@ -214,7 +252,6 @@ fn remove_cookie<'a>(config: &SessionConfig, cookie_type: NameType) -> Cookie<'a
     cookie
 }
 
-#[cfg(not(feature = "rest_mode"))]
 /// This will get a CookieJar from the Headers.
 pub(crate) fn get_cookies(headers: &HeaderMap) -> CookieJar {
     let mut jar = CookieJar::new();
@@ -233,32 +270,75 @@ pub(crate) fn get_cookies(headers: &HeaderMap) -> CookieJar {
     jar
 }
 
-#[cfg(feature = "rest_mode")]
-/// This will get a Hashmap of all the headers that Exist.
-pub(crate) fn get_headers<T>(
+/// Gets a header's value if it is valid utf8.
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Gets the token from an `Authorization: Bearer <token>` header.
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let (scheme, token) = header_value(headers, AUTHORIZATION.as_str())?.split_once(' ')?;
+
+    if scheme.eq_ignore_ascii_case("bearer") {
+        Some(token.trim())
+    } else {
+        None
+    }
+}
+
+/// Signs the Session ID if a Key is set so it can be sent within a header or response body.
+pub(crate) fn session_token<T>(session: &Session<T>, ip_user_agent: &str) -> String
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    if let Some(key) = session.store.config.cookie_and_header.key.as_ref() {
+        match sign_header(&session.id, key, ip_user_agent) {
+            Ok(v) => v,
+            Err(err) => {
+                tracing::error!(err = %err, "Failed to sign Session ID so blank will be used.");
+                String::new()
+            }
+        }
+    } else {
+        session.id.clone()
+    }
+}
+
+/// Gets the Session ID and if the Session is storable using the transport.
+/// `SessionTransport::CookieThenHeader` only uses the header if no Session cookie was sent.
+/// Also returns the transport the Session ID was found in so the response only sets that one.
+/// `CookieThenHeader` is returned when the request sent neither as the client could use either.
+pub(crate) fn get_headers_and_key<T>(
     store: &SessionStore<T>,
+    transport: SessionTransport,
+    cookies: &CookieJar,
     headers: &HeaderMap,
-) -> HashMap<String, String>
+    ip_user_agent: &str,
+) -> (Option<String>, bool, SessionTransport)
 where
     T: DatabasePool + Clone + Debug + Sync + Send + 'static,
 {
-    let mut map = HashMap::new();
-
-    for name in [
-        store.config.cookie_and_header.session_name.to_string(),
-        store.config.cookie_and_header.store_name.to_string(),
-    ] {
-        if let Some(value) = headers.get(&name) {
-            if let Ok(val) = value.to_str() {
-                map.insert(name, val.to_owned());
+    match transport {
+        SessionTransport::Cookie => {
+            let (value, storable) = get_cookie_and_key(store, cookies, ip_user_agent);
+            (value, storable, transport)
+        }
+        SessionTransport::Header => {
+            let (value, storable) = get_header_and_key(store, headers, ip_user_agent);
+            (value, storable, transport)
+        }
+        SessionTransport::CookieThenHeader => {
+            match get_cookie_and_key(store, cookies, ip_user_agent) {
+                (Some(value), storable) => (Some(value), storable, SessionTransport::Cookie),
+                (None, _) => match get_header_and_key(store, headers, ip_user_agent) {
+                    (Some(value), storable) => (Some(value), storable, SessionTransport::Header),
+                    (None, storable) => (None, storable, transport),
+                },
             }
         }
     }
-
-    map
 }
 
-#[cfg(not(feature = "rest_mode"))]
 fn set_cookies(jar: CookieJar, headers: &mut HeaderMap) {
     for cookie in jar.delta() {
         if let Ok(header_value) = cookie.encoded().to_string().parse() {
@@ -270,6 +350,7 @@ fn set_cookies(jar: CookieJar, headers: &mut HeaderMap) {
 /// Used to Set either the Header Values or the Cookie Values.
 pub(crate) fn set_headers<T>(
     session: &Session<T>,
+    transport: SessionTransport,
     headers: &mut HeaderMap,
     ip_user_agent: &str,
     destroy: bool,
@@ -277,13 +358,14 @@ pub(crate) fn set_headers<T>(
 ) where
     T: DatabasePool + Clone + Debug + Sync + Send + 'static,
 {
-    // Lets make a new jar as we only want to add our cookies to the Response cookie header.\
-    #[cfg(not(feature = "rest_mode"))]
-    {
+    // Lets make a new jar as we only want to add our cookies to the Response cookie header.
+    if transport.uses_cookies() {
         let mut cookies = CookieJar::new();
 
-        // Add SessionID
-        if (storable || !session.store.config.session_mode.is_opt_in()) && !destroy {
+        // Add SessionID. Sessions stored within the cookies use `set_cookie_session` instead.
+        if session.store.is_cookie_store() {
+            // The ID is within the encrypted Session cookies.
+        } else if (storable || !session.store.config.session_mode.is_opt_in()) && !destroy {
             cookies.add_cookie(
                 create_cookie(&session.store.config, session.id.clone(), NameType::Data),
                 &session.store.config.cookie_and_header.key,
@@ -318,25 +400,21 @@ pub(crate) fn set_headers<T>(
 
         set_cookies(cookies, headers);
     }
-    #[cfg(feature = "rest_mode")]
-    {
-        use crate::sec::sign_header;
+
+    if transport.uses_headers() {
         // Add SessionID
         if (storable || !session.store.config.session_mode.is_opt_in()) && !destroy {
-            let name = NameType::Data.get_name(&session.store.config);
-            let value = if let Some(key) = session.store.config.cookie_and_header.key.as_ref() {
-                match sign_header(&session.id, key, ip_user_agent) {
-                    Ok(v) => v,
-                    Err(err) => {
-                        tracing::error!(err = %err, "Failed to sign Session ID so blank will be used.");
-                        String::new()
-                    }
-                }
-            } else {
-                session.id.clone()
-            };
+            let value = session_token(session, ip_user_agent);
 
-            if let Ok(name) = HeaderName::from_bytes(name.as_bytes()) {
+            if session.store.config.cookie_and_header.bearer_token {
+                if let Ok(value) = HeaderValue::from_str(&format!("Bearer {value}")) {
+                    headers.insert(AUTHORIZATION, value);
+                }
+            } else if let Ok(name) = HeaderName::from_bytes(
+                NameType::Data
+                    .get_name(&session.store.config, false)
+                    .as_bytes(),
+            ) {
                 if let Ok(value) = HeaderValue::from_str(&value) {
                     headers.insert(name, value);
                 }
@@ -345,7 +423,7 @@ pub(crate) fn set_headers<T>(
 
         // Add Session Store Boolean
         if session.store.config.session_mode.is_opt_in() && storable && !destroy {
-            let name = NameType::Store.get_name(&session.store.config);
+            let name = NameType::Store.get_name(&session.store.config, false);
             //storable doesn't need signing or encryption.
             let value = storable.to_string();
 
@@ -358,6 +436,47 @@ pub(crate) fn set_headers<T>(
     }
 }
 
+/// Sets the numbered cookies of a Session stored within the cookies.
+/// None removes all of the cookies the Session was split across.
+pub(crate) fn set_cookie_session(
+    config: &SessionConfig,
+    headers: &mut HeaderMap,
+    value: Option<&str>,
+    previous_chunks: usize,
+) {
+    let mut cookies = CookieJar::new();
+    let mut chunks = 0;
+
+    if let Some(value) = value {
+        // The value is base64 and a key id so it can be split at any byte.
+        let parts: Vec<&str> = value
+            .as_bytes()
+            .chunks(COOKIE_CHUNK_SIZE)
+            .filter_map(|chunk| std::str::from_utf8(chunk).ok())
+            .collect();
+
+        // `serialize_cookie_session` refuses larger Sessions so this only keeps the cookies as they are.
+        if parts.len() > MAX_COOKIE_CHUNKS {
+            return;
+        }
+
+        for part in parts {
+            let mut cookie = create_cookie(config, part.to_owned(), NameType::Data);
+            cookie.set_name(cookie_chunk_name(config, chunks));
+            cookies.add(cookie);
+            chunks += 1;
+        }
+    }
+
+    for index in chunks..previous_chunks {
+        let mut cookie = remove_cookie(config, NameType::Data);
+        cookie.set_name(cookie_chunk_name(config, index));
+        cookies.add(cookie);
+    }
+
+    set_cookies(cookies, headers);
+}
+
 ///Rather than getting a single IP from the x_real, X forwarded and socket ip
 ///It is better to use all 3 to ensure none of them have changed. Setting the default
 /// to be a empty String if it is not present. we will combine these together in a single Message String.
@@ -369,74 +488,115 @@ where
         && store.config.cookie_and_header.with_ip_and_user_agent
     {
         let headers = req.headers();
+        let config = &store.config.ip_user_agent;
+        let to_string = |ip: Option<IpAddr>| ip.map(|ip| ip.to_string()).unwrap_or_default();
+
+        let ip = to_string(config.use_ip.then(|| socket_ip(req)).flatten());
+        let x_forward_for_ip = to_string(
+            config
+                .use_xforward_ip
+                .then(|| x_forwarded_ip(headers))
+                .flatten(),
+        );
+        let forwarded_ip = to_string(
+            config
+                .use_forward_ip
+                .then(|| forwarded_ip(headers))
+                .flatten(),
+        );
+        let real_ip = to_string(config.use_real_ip.then(|| real_ip(headers)).flatten());
+        let user_agent = config
+            .use_user_agent
+            .then(|| user_agent(headers))
+            .flatten()
+            .unwrap_or_default();
 
-        let ip = if store.config.ip_user_agent.use_ip {
-            req.extensions()
-                .get::<axum::extract::ConnectInfo<SocketAddr>>()
-                .map(|addr| addr.ip().to_string())
-                .unwrap_or_default()
-        } else {
-            "".to_owned()
-        };
+        format!("{ip};{x_forward_for_ip};{forwarded_ip};{real_ip};{user_agent}",)
+    } else {
+        String::new()
+    }
+}
 
-        let x_forward_for_ip = if store.config.ip_user_agent.use_xforward_ip {
-            headers
-                .get(X_FORWARDED_FOR)
-                .and_then(|hv| hv.to_str().ok())
-                .and_then(|s| s.split(',').find_map(|s| s.trim().parse::<IpAddr>().ok()))
-                .map(|ip| ip.to_string())
-                .unwrap_or_default()
-        } else {
-            "".to_owned()
-        };
+/// Gets the clients IP and user agent for the Sessions metadata from the sources
+/// enabled within the `IpUserAgentConfig`. Proxy headers are preferred over the socket IP.
+pub(crate) fn get_client_info<T, D>(
+    req: &Request<T>,
+    store: &SessionStore<D>,
+) -> (Option<IpAddr>, Option<String>)
+where
+    D: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    if !store.config.client_metadata {
+        return (None, None);
+    }
 
-        let forwarded_ip = if store.config.ip_user_agent.use_forward_ip {
-            headers
-                .get_all(FORWARDED)
-                .iter()
-                .find_map(|hv| {
-                    hv.to_str()
-                        .ok()
-                        .and_then(|s| ForwardedHeaderValue::from_forwarded(s).ok())
-                        .and_then(|f| {
-                            f.iter()
-                                .filter_map(|fs| fs.forwarded_for.as_ref())
-                                .find_map(|ff| match ff {
-                                    Identifier::SocketAddr(a) => Some(a.ip()),
-                                    Identifier::IpAddr(ip) => Some(*ip),
-                                    _ => None,
-                                })
-                        })
-                })
-                .map(|ip| ip.to_string())
-                .unwrap_or_default()
-        } else {
-            "".to_owned()
-        };
+    let headers = req.headers();
+    let config = &store.config.ip_user_agent;
+
+    let ip = config
+        .use_forward_ip
+        .then(|| forwarded_ip(headers))
+        .flatten()
+        .or_else(|| {
+            config
+                .use_xforward_ip
+                .then(|| x_forwarded_ip(headers))
+                .flatten()
+        })
+        .or_else(|| config.use_real_ip.then(|| real_ip(headers)).flatten())
+        .or_else(|| config.use_ip.then(|| socket_ip(req)).flatten());
 
-        let real_ip = if store.config.ip_user_agent.use_real_ip {
-            headers
-                .get(X_REAL_IP)
-                .and_then(|hv| hv.to_str().ok())
-                .and_then(|s| s.parse::<IpAddr>().ok())
-                .map(|ip| ip.to_string())
-                .unwrap_or_default()
-        } else {
-            "".to_owned()
-        };
+    let user_agent = config
+        .use_user_agent
+        .then(|| user_agent(headers))
+        .flatten()
+        .map(str::to_owned);
 
-        let user_agent = if store.config.ip_user_agent.use_user_agent {
-            headers
-                .get(USER_AGENT)
-                .and_then(|hv| hv.to_str().ok())
-                .map(|useragent| useragent.to_string())
-                .unwrap_or_default()
-        } else {
-            "".to_owned()
-        };
+    (ip, user_agent)
+}
 
-        format!("{ip};{x_forward_for_ip};{forwarded_ip};{real_ip};{user_agent}",)
-    } else {
-        String::new()
-    }
+#[inline]
+fn socket_ip<T>(req: &Request<T>) -> Option<IpAddr> {
+    req.extensions()
+        .get::<axum::extract::ConnectInfo<SocketAddr>>()
+        .map(|addr| addr.ip())
+}
+
+#[inline]
+fn x_forwarded_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    headers
+        .get(X_FORWARDED_FOR)
+        .and_then(|hv| hv.to_str().ok())
+        .and_then(|s| s.split(',').find_map(|s| s.trim().parse::<IpAddr>().ok()))
+}
+
+#[inline]
+fn forwarded_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    headers.get_all(FORWARDED).iter().find_map(|hv| {
+        hv.to_str()
+            .ok()
+            .and_then(|s| ForwardedHeaderValue::from_forwarded(s).ok())
+            .and_then(|f| {
+                f.iter()
+                    .filter_map(|fs| fs.forwarded_for.as_ref())
+                    .find_map(|ff| match ff {
+                        Identifier::SocketAddr(a) => Some(a.ip()),
+                        Identifier::IpAddr(ip) => Some(*ip),
+                        _ => None,
+                    })
+            })
+    })
+}
+
+#[inline]
+fn real_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    headers
+        .get(X_REAL_IP)
+        .and_then(|hv| hv.to_str().ok())
+        .and_then(|s| s.parse::<IpAddr>().ok())
+}
+
+#[inline]
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers.get(USER_AGENT).and_then(|hv| hv.to_str().ok())
 }
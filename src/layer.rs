@@ -1,8 +1,14 @@
 use std::fmt;
 
-use crate::{DatabasePool, SessionService, SessionStore};
+use crate::{DatabasePool, SessionError, SessionService, SessionStore, SessionTransport};
+use axum::response::Response;
+use http::request::Parts;
 use tower_layer::Layer;
 
+/// Builds the response sent when the `SessionLayer` fails to load, save or remove a Session.
+/// It is given the error and the parts of the request that was being handled.
+pub type SessionErrorHandler = fn(SessionError, &Parts) -> Response;
+
 /// Sessions Layer used with Axum to activate the Service.
 ///
 /// # Examples
@@ -21,6 +27,9 @@ where
     T: DatabasePool + Clone + fmt::Debug + std::marker::Sync + std::marker::Send + 'static,
 {
     session_store: SessionStore<T>,
+    transport: SessionTransport,
+    error_handler: Option<SessionErrorHandler>,
+    keep_response_on_error: bool,
 }
 
 impl<T> SessionLayer<T>
@@ -41,7 +50,74 @@ where
     ///
     #[inline]
     pub fn new(session_store: SessionStore<T>) -> Self {
-        SessionLayer { session_store }
+        SessionLayer {
+            transport: session_store.config.cookie_and_header.transport,
+            session_store,
+            error_handler: None,
+            keep_response_on_error: false,
+        }
+    }
+
+    /// Set's how the Session ID is sent for this layer only.
+    /// This lets routers for browsers and rest APIs share one SessionStore.
+    /// Default is the transport set within the `SessionConfig`.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore, SessionLayer, SessionTransport};
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config).await.unwrap();
+    /// let layer = SessionLayer::new(session_store).with_transport(SessionTransport::Header);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_transport(mut self, transport: SessionTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Set's the handler that builds the response when the layer fails to generate an ID,
+    /// or load, save or remove a Session. Errors are still logged before it is called.
+    /// Default is an empty `500 Internal Server Error` response.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum::response::IntoResponse;
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore, SessionLayer};
+    /// use http::StatusCode;
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config).await.unwrap();
+    /// let layer = SessionLayer::new(session_store).with_error_handler(|_err, _parts| {
+    ///     (StatusCode::SERVICE_UNAVAILABLE, "Sessions are unavailable.").into_response()
+    /// });
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_error_handler(mut self, handler: SessionErrorHandler) -> Self {
+        self.error_handler = Some(handler);
+        self
+    }
+
+    /// Set's if the handler's response is kept when saving, renewing or removing the Session fails
+    /// after the handler ran. The error is only logged and the error handler is not called.
+    /// Failures before the handler runs always use the error handler since there is no response yet.
+    /// Default is false.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore, SessionLayer};
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config).await.unwrap();
+    /// let layer = SessionLayer::new(session_store).with_keep_response_on_error(true);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_keep_response_on_error(mut self, enable: bool) -> Self {
+        self.keep_response_on_error = enable;
+        self
     }
 }
 
@@ -54,6 +130,9 @@ where
     fn layer(&self, inner: S) -> Self::Service {
         SessionService {
             session_store: self.session_store.clone(),
+            transport: self.transport,
+            error_handler: self.error_handler,
+            keep_response_on_error: self.keep_response_on_error,
             inner,
         }
     }
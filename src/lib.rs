@@ -4,24 +4,54 @@
 #![forbid(unsafe_code)]
 #![cfg_attr(docsrs, feature(doc_auto_cfg, doc_cfg))]
 
+mod codec;
+mod compression;
 mod config;
+mod csrf;
 pub mod databases;
 mod errors;
+mod flash;
 pub(crate) mod headers;
 mod layer;
+mod listener;
+mod logging;
+mod metadata;
 mod sec;
 mod service;
 mod session;
 mod session_data;
+mod session_key;
+mod session_metrics;
 mod session_store;
+mod token;
 
-pub use config::{IdGenerator, Key, SameSite, SessionConfig, SessionMode};
+#[cfg(feature = "bincode")]
+pub use codec::BincodeCodec;
+#[cfg(feature = "cbor")]
+pub use codec::CborCodec;
+#[cfg(feature = "msgpack")]
+pub use codec::MessagePackCodec;
+pub use codec::{JsonCodec, SessionCodec, StoredSession};
+pub use compression::SessionCompression;
+pub use config::{
+    ConflictPolicy, DatabaseFailurePolicy, IdGenerator, Key, MemoryCapacity, OwnerLimitPolicy,
+    SameSite, SessionConfig, SessionMode, SessionTransport,
+};
+pub use csrf::{CsrfLayer, CsrfService, CsrfToken};
 pub use databases::*;
 pub use errors::SessionError;
-pub use layer::SessionLayer;
+pub use flash::{FlashLevel, FlashMessage, Flashes};
+pub use layer::{SessionErrorHandler, SessionLayer};
+pub use listener::SessionListener;
+pub use metadata::{SessionMetadata, SessionUserAgent};
 pub use sec::*;
 pub use session::{ReadOnlySession, Session};
-pub use session_store::SessionStore;
+pub use session_key::SessionKey;
+#[cfg(feature = "metrics")]
+pub use session_metrics::describe_metrics;
+pub use session_store::{MaintenanceHandle, SessionStore};
+pub use token::SessionToken;
 
 pub(crate) use service::SessionService;
-pub(crate) use session_data::{SessionData, SessionTimers};
+pub(crate) use session_data::SessionData;
+pub(crate) use session_data::SessionTimers;
@@ -0,0 +1,52 @@
+use async_trait::async_trait;
+use std::fmt::Debug;
+
+/// Receives the lifecycle events of the Sessions within a `SessionStore`.
+/// Register it using `SessionStore::with_listener`.
+///
+/// Every method does nothing by default so only the needed events have to be implemented.
+/// They are awaited within the request or sweep that caused them so they should return quickly.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::SessionListener;
+///
+/// #[derive(Debug)]
+/// struct AuditLog;
+///
+/// #[async_trait::async_trait]
+/// impl SessionListener for AuditLog {
+///     async fn on_renew(&self, old_id: &str, new_id: &str) {
+///         println!("Session {old_id} was renewed as {new_id}");
+///     }
+/// }
+/// ```
+///
+#[async_trait]
+pub trait SessionListener: Debug + Send + Sync + 'static {
+    /// Called when the `SessionLayer` creates new Session data.
+    async fn on_create(&self, id: &str) {
+        let _ = id;
+    }
+
+    /// Called when the `SessionLayer` loads a Session from the database or cookies.
+    async fn on_load(&self, id: &str) {
+        let _ = id;
+    }
+
+    /// Called after a renewed Session was given its new ID.
+    async fn on_renew(&self, old_id: &str, new_id: &str) {
+        let _ = (old_id, new_id);
+    }
+
+    /// Called after a Session was destroyed.
+    async fn on_destroy(&self, id: &str) {
+        let _ = id;
+    }
+
+    /// Called with the Sessions removed by an expiry sweep.
+    /// Databases that expire Sessions on their own, like Redis, do not report them.
+    async fn on_expire(&self, ids: &[String]) {
+        let _ = ids;
+    }
+}
@@ -0,0 +1,62 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::OnceLock,
+};
+use tracing::Level;
+
+/// Random key for the life of the process so a Session always logs as the same hash
+/// until the process restarts, without the hash being reversible or guessable.
+static LOG_KEY: OnceLock<[u8; 32]> = OnceLock::new();
+
+/// Displays a short keyed hash of a Session ID so logs never contain the ID itself.
+/// The hash is only computed if the log or span is actually recorded.
+pub(crate) struct LogId<'a>(pub(crate) &'a str);
+
+impl Display for LogId<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let key = LOG_KEY.get_or_init(rand::random);
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC can take a key of any size");
+        mac.update(self.0.as_bytes());
+
+        mac.finalize().into_bytes()[..8]
+            .iter()
+            .try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// Logs the chatty success messages at the level set by `SessionConfig::with_log_level`.
+pub(crate) fn log(level: Level, session: Option<&str>, message: &str) {
+    let session = session.map(|id| tracing::field::display(LogId(id)));
+
+    macro_rules! event {
+        ($level:expr) => {
+            tracing::event!($level, session, "{message}")
+        };
+    }
+
+    match level {
+        Level::TRACE => event!(Level::TRACE),
+        Level::DEBUG => event!(Level::DEBUG),
+        Level::INFO => event!(Level::INFO),
+        Level::WARN => event!(Level::WARN),
+        _ => event!(Level::ERROR),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LogId;
+
+    #[test]
+    fn log_ids_are_stable_hashes() {
+        let id = "4a0b3c27-5f0e-4bd2-9c1b-1e2d0f6c9a11";
+        let hash = LogId(id).to_string();
+
+        assert_eq!(hash.len(), 16);
+        assert!(!hash.contains(id));
+        assert_eq!(hash, LogId(id).to_string());
+        assert_ne!(hash, LogId("another-session").to_string());
+    }
+}
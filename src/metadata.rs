@@ -0,0 +1,150 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+/// Information about the client and lifetime of a Session, used to show a user their signed in devices.
+/// Returned by `Session::metadata`, `SessionStore::get_metadata` and `SessionStore::list_metadata_by_owner`.
+///
+/// The IP and user agent are only kept while `SessionConfig::with_client_metadata` is enabled
+/// and are read from the sources enabled within the `IpUserAgentConfig`.
+/// When `SessionConfig::with_touch_interval` is set, `last_seen` is stored at most once per
+/// interval for Sessions with an owner, and only alongside other changes for Sessions without one.
+///
+/// # Examples
+/// ```rust ignore
+/// let metadata = session.metadata();
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    /// The ID of the Session.
+    pub id: String,
+    /// When the Session was first created.
+    pub created_at: DateTime<Utc>,
+    /// When the Session was last used by a request.
+    pub last_seen: DateTime<Utc>,
+    /// When the owner last logged in using `Session::login`.
+    pub authenticated_at: Option<DateTime<Utc>>,
+    /// The clients IP with the host part removed. A /24 for IPv4 and a /48 for IPv6.
+    pub ip: Option<String>,
+    /// The browser and operating system read from the clients user agent.
+    pub user_agent: Option<SessionUserAgent>,
+}
+
+/// The browser and operating system of a client, parsed from its user agent.
+/// Only these are kept so the full user agent is never stored.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::SessionUserAgent;
+///
+/// let agent = SessionUserAgent::parse(
+///     "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1",
+/// );
+///
+/// assert_eq!(agent.browser.as_deref(), Some("Safari"));
+/// assert_eq!(agent.os.as_deref(), Some("iOS"));
+/// assert!(agent.mobile);
+/// ```
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionUserAgent {
+    /// The browser's name if it was recognized.
+    pub browser: Option<String>,
+    /// The operating system's name if it was recognized.
+    pub os: Option<String>,
+    /// If the client said it is a mobile device.
+    pub mobile: bool,
+}
+
+impl SessionUserAgent {
+    /// Parses the browser and operating system from a user agent.
+    /// The checks are ordered since most browsers also claim to be the browsers they are based on.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::SessionUserAgent;
+    ///
+    /// let agent = SessionUserAgent::parse("Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0");
+    /// ```
+    ///
+    pub fn parse(user_agent: &str) -> Self {
+        let has = |needles: &[&str]| needles.iter().any(|needle| user_agent.contains(needle));
+
+        let browser = [
+            (&["Edg/", "EdgA/", "EdgiOS/"][..], "Edge"),
+            (&["OPR/", "Opera"][..], "Opera"),
+            (&["SamsungBrowser/"][..], "Samsung Internet"),
+            (&["Firefox/", "FxiOS/"][..], "Firefox"),
+            (&["Chrome/", "CriOS/", "Chromium/"][..], "Chrome"),
+            (&["Safari/"][..], "Safari"),
+        ]
+        .iter()
+        .find(|(needles, _)| has(needles))
+        .map(|(_, name)| (*name).to_owned());
+
+        let os = [
+            (&["Windows"][..], "Windows"),
+            (&["iPhone", "iPad", "iPod"][..], "iOS"),
+            (&["Android"][..], "Android"),
+            (&["CrOS"][..], "ChromeOS"),
+            (&["Macintosh", "Mac OS X"][..], "macOS"),
+            (&["Linux"][..], "Linux"),
+        ]
+        .iter()
+        .find(|(needles, _)| has(needles))
+        .map(|(_, name)| (*name).to_owned());
+
+        Self {
+            browser,
+            os,
+            mobile: has(&["Mobi", "iPhone"]),
+        }
+    }
+}
+
+/// Removes the host part of the IP so only the network it came from is kept.
+#[inline]
+pub(crate) fn coarse_ip(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{a}.{b}.{c}.0")
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => coarse_ip(IpAddr::V4(ip)),
+            None => {
+                let [a, b, c, ..] = ip.segments();
+                format!("{a:x}:{b:x}:{c:x}::")
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{coarse_ip, SessionUserAgent};
+
+    #[test]
+    fn parses_user_agents_and_coarsens_ips() {
+        let chrome = SessionUserAgent::parse("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36");
+        assert_eq!(chrome.browser.as_deref(), Some("Chrome"));
+        assert_eq!(chrome.os.as_deref(), Some("Windows"));
+        assert!(!chrome.mobile);
+
+        let edge = SessionUserAgent::parse("Mozilla/5.0 (Linux; Android 14) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Mobile Safari/537.36 EdgA/126.0.0.0");
+        assert_eq!(edge.browser.as_deref(), Some("Edge"));
+        assert_eq!(edge.os.as_deref(), Some("Android"));
+        assert!(edge.mobile);
+
+        assert_eq!(coarse_ip("203.0.113.42".parse().unwrap()), "203.0.113.0");
+        assert_eq!(
+            coarse_ip("2001:db8:85a3::8a2e:370:7334".parse().unwrap()),
+            "2001:db8:85a3::"
+        );
+        assert_eq!(
+            coarse_ip("::ffff:198.51.100.7".parse().unwrap()),
+            "198.51.100.0"
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub(crate) mod csrf;
 pub(crate) mod encrypt;
 mod signed;
 
@@ -0,0 +1,71 @@
+use base64::{engine::general_purpose, Engine as _};
+use rand::RngCore;
+
+pub(crate) const TOKEN_LEN: usize = 32;
+
+/// Generates a new random CSRF token.
+pub(crate) fn generate_token() -> String {
+    let mut token = [0u8; TOKEN_LEN];
+    rand::rng().fill_bytes(&mut token);
+    general_purpose::URL_SAFE_NO_PAD.encode(token)
+}
+
+/// Masks the token with a random one time pad so it is different within every response.
+/// This stops BREACH from being able to guess the token from compressed responses.
+pub(crate) fn mask(token: &str) -> String {
+    let Ok(token) = general_purpose::URL_SAFE_NO_PAD.decode(token) else {
+        return String::new();
+    };
+
+    let mut data = vec![0u8; token.len() * 2];
+    let (pad, masked) = data.split_at_mut(token.len());
+    rand::rng().fill_bytes(pad);
+
+    for ((m, t), p) in masked.iter_mut().zip(token.iter()).zip(pad.iter()) {
+        *m = t ^ p;
+    }
+
+    general_purpose::URL_SAFE_NO_PAD.encode(data)
+}
+
+/// Checks that a masked token sent by the client matches the Sessions token.
+pub(crate) fn verify(token: &str, masked: &str) -> bool {
+    let (Ok(token), Ok(data)) = (
+        general_purpose::URL_SAFE_NO_PAD.decode(token),
+        general_purpose::URL_SAFE_NO_PAD.decode(masked.trim()),
+    ) else {
+        return false;
+    };
+
+    if token.is_empty() || data.len() != token.len() * 2 {
+        return false;
+    }
+
+    let (pad, masked) = data.split_at(token.len());
+
+    // Compare in constant time so the token can not be guessed by timing.
+    masked
+        .iter()
+        .zip(pad.iter())
+        .zip(token.iter())
+        .fold(0u8, |diff, ((m, p), t)| diff | (m ^ p ^ t))
+        == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::{generate_token, mask, verify};
+
+    #[test]
+    fn masked_tokens_verify() {
+        let token = generate_token();
+        let first = mask(&token);
+        let second = mask(&token);
+
+        assert_ne!(first, second);
+        assert!(verify(&token, &first));
+        assert!(verify(&token, &second));
+        assert!(!verify(&generate_token(), &first));
+        assert!(!verify(&token, &token));
+    }
+}
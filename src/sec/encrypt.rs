@@ -4,10 +4,32 @@ use aes_gcm::Aes256Gcm;
 use base64::{engine::general_purpose, Engine as _};
 use cookie::Key;
 use rand::TryRngCore;
+use sha2::{Digest, Sha256};
 
 pub(crate) const NONCE_LEN: usize = 12;
 pub(crate) const TAG_LEN: usize = 16;
 pub(crate) const KEY_LEN: usize = 32;
+/// Separates the Key ID from the encrypted data. This is never part of the base64 alphabet.
+pub(crate) const KEY_ID_SEPARATOR: char = ':';
+
+/// Creates a short ID for the Key so we know which Key encrypted a value.
+/// This is a truncated hash so the Key itself can not be recovered from it.
+pub(crate) fn key_id(key: &Key) -> String {
+    Sha256::digest(key.encryption())
+        .iter()
+        .take(4)
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Splits the Key ID from the encrypted data.
+/// Values stored before Key IDs existed return None for the Key ID.
+pub(crate) fn split_key_id(value: &str) -> (Option<&str>, &str) {
+    match value.split_once(KEY_ID_SEPARATOR) {
+        Some((id, data)) => (Some(id), data),
+        None => (None, value),
+    }
+}
 
 ///Used to encrypt the database Values
 pub(crate) fn encrypt(name: &str, value: &str, key: &Key) -> Result<String, &'static str> {
@@ -33,11 +55,16 @@ pub(crate) fn encrypt(name: &str, value: &str, key: &Key) -> Result<String, &'st
 
     tag.copy_from_slice(aad_tag.as_slice());
 
-    Ok(general_purpose::STANDARD.encode(&data))
+    Ok(format!(
+        "{}{KEY_ID_SEPARATOR}{}",
+        key_id(key),
+        general_purpose::STANDARD.encode(&data)
+    ))
 }
 
 ///Used to decrypt the database Values.
 pub(crate) fn decrypt(name: &str, value: &str, key: &Key) -> Result<String, SessionError> {
+    let (_, value) = split_key_id(value);
     let data = general_purpose::STANDARD.decode(value)?;
     if data.len() <= NONCE_LEN {
         return Err(SessionError::GenericNotSupportedError(
@@ -61,3 +88,67 @@ pub(crate) fn decrypt(name: &str, value: &str, key: &Key) -> Result<String, Sess
             })?,
     )?)
 }
+
+///Used to decrypt the database Values using the Key that matches the values Key ID.
+///Values without a Key ID are tried against every Key.
+///Also returns true if the value needs to be encrypted again with the active Key.
+pub(crate) fn decrypt_with_retired(
+    name: &str,
+    value: &str,
+    key: &Key,
+    retired_keys: &[Key],
+) -> Result<(String, bool), SessionError> {
+    let (id, _) = split_key_id(value);
+    let mut last_err = None;
+
+    for (index, ring_key) in std::iter::once(key).chain(retired_keys.iter()).enumerate() {
+        if id.is_some_and(|id| id != key_id(ring_key)) {
+            continue;
+        }
+
+        match decrypt(name, value, ring_key) {
+            Ok(v) => return Ok((v, index > 0 || id.is_none())),
+            Err(err) => last_err = Some(err),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| {
+        SessionError::GenericNotSupportedError(
+            "no database key matches the encrypted values key id".to_owned(),
+        )
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decrypt_with_retired, encrypt};
+    use base64::{engine::general_purpose, Engine as _};
+    use cookie::Key;
+
+    #[test]
+    fn retired_keys_still_decrypt() {
+        let old_key = Key::generate();
+        let new_key = Key::generate();
+
+        let value = encrypt("id", "data", &old_key).unwrap();
+        assert!(decrypt_with_retired("id", &value, &new_key, &[]).is_err());
+        assert_eq!(
+            decrypt_with_retired("id", &value, &new_key, std::slice::from_ref(&old_key)).unwrap(),
+            ("data".to_owned(), true)
+        );
+
+        let value = encrypt("id", "data", &new_key).unwrap();
+        assert_eq!(
+            decrypt_with_retired("id", &value, &new_key, std::slice::from_ref(&old_key)).unwrap(),
+            ("data".to_owned(), false)
+        );
+
+        // Values stored before key ids existed have no prefix.
+        let (_, legacy) = value.split_once(':').unwrap();
+        assert!(general_purpose::STANDARD.decode(legacy).is_ok());
+        assert_eq!(
+            decrypt_with_retired("id", legacy, &old_key, &[new_key]).unwrap(),
+            ("data".to_owned(), true)
+        );
+    }
+}
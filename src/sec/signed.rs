@@ -49,6 +49,7 @@ impl CookiesAdditionJar for CookieJar {
 pub struct AdditionalSignedJar<J> {
     parent: J,
     key: [u8; KEY_LEN],
+    retired_keys: Vec<[u8; KEY_LEN]>,
     message: String,
 }
 
@@ -58,10 +59,21 @@ impl<J> AdditionalSignedJar<J> {
         AdditionalSignedJar {
             parent,
             key: key.signing().try_into().expect("sign key len"),
+            retired_keys: Vec::new(),
             message,
         }
     }
 
+    /// Sets the retired keys that are still accepted when verifying cookies.
+    /// Cookies are always signed using the active key.
+    pub fn with_retired_keys(mut self, keys: &[Key]) -> AdditionalSignedJar<J> {
+        self.retired_keys = keys
+            .iter()
+            .map(|key| key.signing().try_into().expect("sign key len"))
+            .collect();
+        self
+    }
+
     /// Signs the cookie's value and message providing integrity and authenticity.
     fn sign_cookie(&self, cookie: &mut Cookie) {
         // Compute HMAC-SHA256 of the cookie's value.
@@ -86,6 +98,7 @@ impl<J> AdditionalSignedJar<J> {
     /// Given a signed value `str` where the signature is prepended to `value`,
     /// verifies the signed value and returns it. If there's a problem, returns
     /// an `Err` with a string describing the issue.
+    /// The active key is tried first and then each of the retired keys.
     fn _verify(&self, cookie_value: &str) -> Result<String, &'static str> {
         if !cookie_value.is_char_boundary(BASE64_DIGEST_LEN) {
             return Err("missing or invalid digest");
@@ -94,15 +107,20 @@ impl<J> AdditionalSignedJar<J> {
         // Split [MAC | original-value] into its two parts.
         let (digest_str, value) = cookie_value.split_at(BASE64_DIGEST_LEN);
         let digest = decode(digest_str).map_err(|_| "bad base64 digest")?;
-
-        // Perform the verification.
-        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).map_err(|_| "key is invalid.")?;
         // Add message here so we can check if it matches.
         let message = format!("{}{}", value, &self.message);
-        mac.update(message.as_bytes());
-        mac.verify_slice(&digest)
-            .map(|_| value.to_string())
-            .map_err(|_| "value did not verify")
+
+        for key in std::iter::once(&self.key).chain(self.retired_keys.iter()) {
+            // Perform the verification.
+            let mut mac = Hmac::<Sha256>::new_from_slice(key).map_err(|_| "key is invalid.")?;
+            mac.update(message.as_bytes());
+
+            if mac.verify_slice(&digest).is_ok() {
+                return Ok(value.to_string());
+            }
+        }
+
+        Err("value did not verify")
     }
 
     /// Verifies the authenticity and integrity of `cookie`, returning the
@@ -293,11 +311,54 @@ pub(crate) fn verify_header(
         .map_err(|_| "value did not verify")
 }
 
+/// Verifies the signed header value using the active key and then each of the retired keys.
+pub(crate) fn verify_header_with_retired(
+    header_value: &str,
+    key: &Key,
+    retired_keys: &[Key],
+    message: &str,
+) -> Result<String, &'static str> {
+    std::iter::once(key)
+        .chain(retired_keys.iter())
+        .find_map(|key| verify_header(header_value, key, message).ok())
+        .ok_or("value did not verify")
+}
+
 #[cfg(test)]
 mod test {
-    use crate::sec::signed::CookiesAdditionJar;
+    use crate::sec::signed::{sign_header, verify_header_with_retired, CookiesAdditionJar};
     use cookie::{Cookie, CookieJar, Key};
 
+    #[test]
+    fn retired_keys_still_verify() {
+        let old_key = Key::generate();
+        let new_key = Key::generate();
+
+        let mut jar = CookieJar::new();
+        jar.message_signed_mut(&old_key, "ip".to_owned())
+            .add(("session", "value"));
+
+        assert!(jar
+            .message_signed(&new_key, "ip".to_owned())
+            .get("session")
+            .is_none());
+        assert_eq!(
+            jar.message_signed(&new_key, "ip".to_owned())
+                .with_retired_keys(std::slice::from_ref(&old_key))
+                .get("session")
+                .unwrap()
+                .value(),
+            "value"
+        );
+
+        let header = sign_header("value", &old_key, "ip").unwrap();
+        assert!(verify_header_with_retired(&header, &new_key, &[], "ip").is_err());
+        assert_eq!(
+            verify_header_with_retired(&header, &new_key, &[old_key], "ip").unwrap(),
+            "value"
+        );
+    }
+
     #[test]
     fn roundtrip() {
         // Secret is SHA-256 hash of 'Super secret!' passed through HKDF-SHA256.
@@ -1,18 +1,28 @@
-use crate::{headers::*, DatabasePool, Session, SessionData, SessionError, SessionStore};
-use axum::{response::Response, BoxError};
+use crate::{
+    headers::*,
+    logging::{self, LogId},
+    session_metrics,
+    token::TokenSlot,
+    ConflictPolicy, DatabasePool, Session, SessionData, SessionError, SessionErrorHandler,
+    SessionStore, SessionTransport,
+};
+use axum::{body::Body, response::Response, BoxError};
 use bytes::Bytes;
 use chrono::Utc;
+use dashmap::mapref::entry::Entry;
 #[cfg(feature = "key-store")]
 use fastbloom_rs::Deletable;
 use futures::future::BoxFuture;
-use http::Request;
+use http::{request::Parts, Request};
 use http_body::Body as HttpBody;
 use std::{
     convert::Infallible,
     fmt::{self, Debug, Formatter},
     task::{Context, Poll},
+    time::Instant,
 };
 use tower_service::Service;
+use tracing::Instrument;
 
 #[derive(Clone)]
 pub struct SessionService<S, T>
@@ -20,21 +30,131 @@ where
     T: DatabasePool + Clone + Debug + Sync + Send + 'static,
 {
     pub(crate) session_store: SessionStore<T>,
+    pub(crate) transport: SessionTransport,
+    pub(crate) error_handler: Option<SessionErrorHandler>,
+    pub(crate) keep_response_on_error: bool,
     pub(crate) inner: S,
 }
 
-pub(crate) fn trace_error<ResBody>(
-    err: SessionError,
-    msg: &str,
-) -> Result<Response<ResBody>, Infallible>
+/// Turns failures into responses using the `SessionLayer`'s error handler.
+pub(crate) struct ErrorResponder {
+    handler: Option<SessionErrorHandler>,
+    keep_response: bool,
+    /// Only copied from the request when a handler is set.
+    parts: Option<Parts>,
+}
+
+impl ErrorResponder {
+    /// Logs the error and returns the error handler's response or an empty 500.
+    pub(crate) fn respond(&self, err: SessionError, msg: &str) -> Result<Response, Infallible> {
+        tracing::error!(err = %err, msg);
+
+        if let (Some(handler), Some(parts)) = (self.handler, self.parts.as_ref()) {
+            return Ok(handler(err, parts));
+        }
+
+        let mut res = Response::default();
+        *res.status_mut() = match err {
+            SessionError::SessionLimitReached => http::StatusCode::FORBIDDEN,
+            _ => http::StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Ok(res)
+    }
+
+    /// Used once the inner service has responded. Returns None if the error was only
+    /// logged because the layer keeps the original response.
+    pub(crate) fn respond_after(
+        &self,
+        err: SessionError,
+        msg: &str,
+    ) -> Option<Result<Response, Infallible>> {
+        if self.keep_response {
+            tracing::error!(err = %err, msg);
+            return None;
+        }
+
+        Some(self.respond(err, msg))
+    }
+}
+
+/// Destroys a Session that reached its absolute lifespan and replaces it with a new one.
+async fn expire_session<T>(session: &mut Session<T>, storable: bool) -> Result<(), SessionError>
 where
-    ResBody: HttpBody<Data = Bytes> + Default + Send + 'static,
-    ResBody::Error: Into<BoxError>,
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
 {
-    tracing::error!(err = %err, msg);
-    let mut res = Response::default();
-    *res.status_mut() = http::StatusCode::INTERNAL_SERVER_ERROR;
-    Ok(res)
+    let session_id = Session::generate_id(&session.store).await?;
+
+    if session.store.is_persistent() {
+        session
+            .store
+            .database_remove_session(session.id.clone())
+            .await?;
+    }
+
+    #[cfg(feature = "key-store")]
+    if session.store.config.memory.use_bloom_filters {
+        let mut filter = session.store.filter.write().await;
+        filter.remove(session.id.as_bytes());
+    }
+
+    session.store.memory_remove(&session.id);
+    let old_id = std::mem::replace(&mut session.id, session_id);
+    let sess = SessionData::new(session.id.clone(), storable, &session.store.config);
+    session.store.memory_insert(sess);
+
+    logging::log(
+        session.store.config.log_level,
+        Some(&old_id),
+        "Session reached its absolute lifetime and was replaced.",
+    );
+    session.store.notify_expire(&[old_id]).await;
+    session.store.notify_create(&session.id).await;
+    Ok(())
+}
+
+/// Gives the Session a new ID and removes the old one from the database and the filter.
+async fn renew_session<T>(session: &mut Session<T>) -> Result<(), SessionError>
+where
+    T: DatabasePool + Clone + Debug + Sync + Send + 'static,
+{
+    // Lets change the Session ID and destory the old Session from the database.
+    let session_id = Session::generate_id(&session.store).await?;
+
+    // Lets remove it from the database first.
+    if session.store.is_persistent() {
+        session
+            .store
+            .database_remove_session(session.id.clone())
+            .await?;
+    }
+
+    //lets remove it from the filter. if the bottom fails just means it did not exist or was already unloaded.
+    #[cfg(feature = "key-store")]
+    if session.store.config.memory.use_bloom_filters {
+        let mut filter = session.store.filter.write().await;
+        filter.remove(session.id.as_bytes());
+    }
+
+    // Lets remove update and reinsert.
+    if let Some(mut session_data) = session.store.memory_remove(&session.id) {
+        session_data.id = session_id.clone();
+        session_data.renew = false;
+        // The new ID has never been stored so its version starts over.
+        session_data.version = 0;
+        // The owner index only knows the old ID.
+        session_data.owner_changed = session_data.owner.is_some();
+        session_data.full_write = true;
+        let old_id = std::mem::replace(&mut session.id, session_id);
+        session.store.memory_insert(session_data);
+        session.store.notify_renew(&old_id, &session.id).await;
+        logging::log(
+            session.store.config.log_level,
+            Some(&session.id),
+            "Session was renewed.",
+        );
+    }
+
+    Ok(())
 }
 
 impl<S, T, ReqBody, ResBody> Service<Request<ReqBody>> for SessionService<S, T>
@@ -46,11 +166,11 @@ where
     S::Future: Send + 'static,
     ReqBody: Send + 'static,
     Infallible: From<<S as Service<Request<ReqBody>>>::Error>,
-    ResBody: HttpBody<Data = Bytes> + Default + Send + 'static,
+    ResBody: HttpBody<Data = Bytes> + Send + 'static,
     ResBody::Error: Into<BoxError>,
     T: DatabasePool + Clone + Debug + Sync + Send + 'static,
 {
-    type Response = Response<ResBody>;
+    type Response = Response;
     type Error = Infallible;
     type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
 
@@ -60,161 +180,300 @@ where
 
     fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
         let store = self.session_store.clone();
+        let transport = self.transport;
+        let error_handler = self.error_handler;
+        let keep_response_on_error = self.keep_response_on_error;
         let not_ready_inner = self.inner.clone();
         let mut ready_inner = std::mem::replace(&mut self.inner, not_ready_inner);
 
         Box::pin(async move {
+            let start = Instant::now();
+            session_metrics::increment(
+                session_metrics::REQUESTS,
+                &store.config.database.table_name,
+                1,
+            );
+
+            // The error handler is given the request's parts so they are kept for it.
+            let parts = if error_handler.is_some() {
+                let (parts, body) = req.into_parts();
+                let copy = parts.clone();
+                req = Request::from_parts(parts, body);
+                Some(copy)
+            } else {
+                None
+            };
+
+            let errors = ErrorResponder {
+                handler: error_handler,
+                keep_response: keep_response_on_error,
+                parts,
+            };
+
+            // While degraded this replays the queued writes once the database is back.
+            store.retry_database().await;
+
             let ip_user_agent = get_ips_hash(&req, &store);
+            let (client_ip, user_agent) = get_client_info(&req, &store);
+
+            if store.is_cookie_store() && !transport.uses_cookies() {
+                return errors.respond(
+                    SessionError::GenericNotSupportedError(
+                        "SessionCookiePool needs a transport that uses cookies.".to_owned(),
+                    ),
+                    "failed to load session from the cookies",
+                );
+            }
 
-            #[cfg(not(feature = "rest_mode"))]
             let cookies = get_cookies(req.headers());
 
-            #[cfg(not(feature = "rest_mode"))]
-            let (session_id, storable) = get_headers_and_key(&store, cookies, &ip_user_agent).await;
+            // Sessions stored within the cookies are loaded into memory for the request.
+            let (cookie_session, cookie_chunks) = if store.is_cookie_store() {
+                get_cookie_session(&store, &cookies)
+            } else {
+                (None, 0)
+            };
+
+            let (session_id, storable, transport) =
+                get_headers_and_key(&store, transport, &cookies, req.headers(), &ip_user_agent);
 
-            #[cfg(feature = "rest_mode")]
-            let headers = get_headers(&store, req.headers());
+            // The Session came from the cookies so a `CookieThenHeader` response only sets those.
+            let transport = if cookie_session.is_some() {
+                SessionTransport::Cookie
+            } else {
+                transport
+            };
 
-            #[cfg(feature = "rest_mode")]
-            let (session_id, storable) = get_headers_and_key(&store, headers, &ip_user_agent).await;
+            let session_id = if store.is_cookie_store() {
+                match cookie_session {
+                    Some(mut sess) => {
+                        let id = sess.id.clone();
+                        sess.store = storable;
+
+                        // A request already using this Session keeps its newer copy and request count.
+                        let inserted = match store.inner.entry(id.clone()) {
+                            Entry::Occupied(_) => false,
+                            Entry::Vacant(entry) => {
+                                store.memory_count(&mut sess);
+                                entry.insert(sess);
+                                true
+                            }
+                        };
+
+                        if inserted {
+                            store.notify_load(&id).await;
+                        }
+
+                        Some(id)
+                    }
+                    None => None,
+                }
+            } else {
+                session_id
+            };
 
             let (mut session, is_new) = match Session::new(store, session_id).await {
                 Ok(v) => v,
                 Err(err) => {
-                    return trace_error(err, "failed to generate Session ID");
+                    return errors.respond(err, "failed to generate Session ID");
                 }
             };
 
             // Check if the session id exists if not lets check if it exists in the database or generate a new session.
             // If manual mode is enabled then do not check for a Session unless the ID is not new.
+            let table = &session.store.config.database.table_name;
             let check_database: bool = if is_new && !session.store.config.session_mode.is_manual() {
                 let sess = SessionData::new(session.id.clone(), storable, &session.store.config);
-                session.store.inner.insert(session.id.clone(), sess);
+                session.store.memory_insert(sess);
+                session.store.notify_create(&session.id).await;
+                session_metrics::increment(session_metrics::MEMORY_MISSES, table, 1);
                 false
             } else if !is_new || !session.store.config.session_mode.is_manual() {
-                !session.store.service_session_data(&session)
+                let loaded = session.store.service_session_data(&session);
+                session_metrics::increment(
+                    if loaded {
+                        session_metrics::MEMORY_HITS
+                    } else {
+                        session_metrics::MEMORY_MISSES
+                    },
+                    table,
+                    1,
+                );
+                !loaded
             } else {
                 false
             };
 
             if check_database {
-                let mut fresh_session = session
+                let loaded = session
                     .store
                     .load_session(session.id.clone())
-                    .await
-                    .ok()
-                    .flatten()
-                    .unwrap_or_else(|| {
-                        tracing::info!(
-                            "Session {} did not exist in Database. So it was Recreated.",
-                            session.id.clone()
+                    .instrument(tracing::info_span!(
+                        "session_load",
+                        session = %LogId(&session.id)
+                    ))
+                    .await;
+
+                // The database might still have it if it could not be read.
+                let recreated = loaded.is_err() || session.store.is_degraded();
+
+                let mut fresh_session = match loaded.ok().flatten() {
+                    Some(sess) => {
+                        session.store.notify_load(&session.id).await;
+                        sess
+                    }
+                    None => {
+                        logging::log(
+                            session.store.config.log_level,
+                            Some(&session.id),
+                            "Session did not exist in Database. So it was Recreated.",
                         );
-                        SessionData::new(session.id.clone(), storable, &session.store.config)
-                    });
+                        session.store.notify_create(&session.id).await;
+                        let mut sess =
+                            SessionData::new(session.id.clone(), storable, &session.store.config);
+                        sess.recreated = recreated;
+                        sess
+                    }
+                };
 
                 fresh_session.autoremove = Utc::now() + session.store.config.memory.memory_lifespan;
                 fresh_session.store = storable;
                 fresh_session.update = true;
                 fresh_session.requests = 1;
-                session
-                    .store
-                    .inner
-                    .insert(session.id.clone(), fresh_session);
+                session.store.memory_insert(fresh_session);
             }
 
-            let (last_sweep, last_database_sweep) = {
-                let timers = session.store.timers.read().await;
-                (timers.last_expiry_sweep, timers.last_database_expiry_sweep)
-            };
+            // Loading a Session into memory can push it past its capacity.
+            if check_database || (is_new && !session.store.config.session_mode.is_manual()) {
+                session.store.enforce_memory_capacity().await;
+            }
 
-            // This branch runs less often, and we already have write access,
-            // let's check if any sessions expired. We don't want to hog memory
-            // forever by abandoned sessions (e.g. when a client lost their cookie)
-            // throttle by memory lifespan - e.g. sweep every hour
-            let current_time = Utc::now();
+            // Sessions past their absolute lifespan are replaced no matter how active they are.
+            let past_lifespan = session
+                .store
+                .inner
+                .get(&session.id)
+                .is_some_and(|sess| sess.past_absolute_lifespan(&session.store.config));
 
-            if last_sweep <= current_time && !session.store.config.memory.memory_lifespan.is_zero()
-            {
-                tracing::info!(
-                    "Session id {}: Session Memory Cleaning Started",
-                    session.id.clone()
-                );
-                // Only unload these from filter if the Client is None as this means no database.
-                // Otherwise only unload from the filter if removed from the Database.
-                #[cfg(feature = "key-store")]
-                if session.store.is_persistent()
-                    && session.store.auto_handles_expiry()
-                    && session.store.config.memory.use_bloom_filters
+            if past_lifespan {
+                let span = tracing::info_span!("session_expire", session = %LogId(&session.id));
+
+                if let Err(err) = expire_session(&mut session, storable)
+                    .instrument(span)
+                    .await
                 {
-                    let mut filter = session.store.filter.write().await;
+                    return errors.respond(err, "failed to replace expired session");
+                }
+            }
+
+            if let Some(mut sess) = session.store.inner.get_mut(&session.id) {
+                sess.seen(client_ip, user_agent.as_deref());
+            }
+
+            // Run the memory and database sweeps here unless a maintenance task handles them.
+            if session.store.config.inline_maintenance {
+                if let Err(err) = session.store.run_maintenance().await {
+                    return errors.respond(err, "failed to remove expired session's from database");
+                }
+            }
+
+            // Keep a copy of the data before the request changes it so we can merge on a version conflict.
+            let original = if matches!(
+                session.store.config.database.conflict_policy,
+                ConflictPolicy::RetryWithMerge(_)
+            ) {
+                Some(
                     session
                         .store
                         .inner
-                        .iter()
-                        .filter(|r| r.autoremove < current_time)
-                        .for_each(|r| filter.remove(r.key().as_bytes()));
-                }
+                        .get(&session.id)
+                        .map(|sess| sess.data.clone())
+                        .unwrap_or_default(),
+                )
+            } else {
+                None
+            };
+
+            // Kept to find out if the handler changed the owner.
+            let owner = session
+                .store
+                .inner
+                .get(&session.id)
+                .and_then(|sess| sess.owner.clone());
 
+            // With an owner limit the whole Session is kept so a refused login can be rolled back.
+            let mut snapshot = if session.store.config.max_sessions_per_owner.is_some() {
                 session
                     .store
                     .inner
-                    .retain(|_k, v| v.autoremove > current_time);
+                    .get(&session.id)
+                    .map(|sess| sess.clone())
+            } else {
+                None
+            };
 
-                session.store.timers.write().await.last_expiry_sweep =
-                    Utc::now() + session.store.config.memory.purge_update;
-                tracing::info!(
-                    "Session id {}: Session Memory Cleaning Finished",
-                    session.id
-                );
-            }
+            // Sets a clone of the Store in the Extensions for Direct usage and sets the Session for Direct usage
+            //req.extensions_mut().insert(store.clone());
+            req.extensions_mut().insert(session.clone());
 
-            // Throttle by database lifespan - e.g. sweep every 6 hours
-            if last_database_sweep <= current_time && session.store.is_persistent() {
-                tracing::info!(
-                    "Session id {}: Session Database Cleaning Started",
-                    session.id
-                );
-                //Remove any old keys that expired and Remove them from our loaded filter.
-                #[cfg(feature = "key-store")]
-                let expired = match session.store.cleanup().await {
-                    Ok(v) => v,
-                    Err(err) => {
-                        return trace_error(
-                            err,
-                            "failed to remove expired session's from database",
+            let mut response = ready_inner.call(req).await?.map(Body::new);
+
+            // The handler may have changed how much memory the Session uses.
+            session.store.memory_recount(&session.id);
+
+            let new_owner = match session.store.inner.get(&session.id) {
+                Some(sess) if sess.owner != owner => {
+                    // Debug builds warn if the owner changed without renewing the Session ID.
+                    #[cfg(debug_assertions)]
+                    if !sess.renew && !sess.destroy {
+                        tracing::warn!(
+                            session = %LogId(&session.id),
+                            "Session owner changed without renewing the Session ID. Use Session::login or Session::renew to prevent session fixation."
                         );
                     }
-                };
 
-                #[cfg(not(feature = "key-store"))]
-                if let Err(err) = session.store.cleanup().await {
-                    return trace_error(err, "failed to remove expired session's from database");
+                    sess.owner.clone()
                 }
+                _ => None,
+            };
 
-                #[cfg(feature = "key-store")]
-                if !session.store.auto_handles_expiry() {
-                    let mut filter = session.store.filter.write().await;
-                    expired.iter().for_each(|id| filter.remove(id.as_bytes()));
-                }
+            let mut refused = false;
 
-                session
+            if let Some(new_owner) = new_owner {
+                match session
                     .store
-                    .timers
-                    .write()
+                    .enforce_owner_limit(&session.id, &new_owner)
                     .await
-                    .last_database_expiry_sweep =
-                    Utc::now() + session.store.config.database.purge_database_update;
-                tracing::info!(
-                    "Session id {}: Session Database Cleaning Finished",
-                    session.id
-                );
-            }
+                {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        // The refused login is undone and nothing from it is renewed or stored.
+                        if let (Some(mut sess), Some(snapshot)) =
+                            (session.store.inner.get_mut(&session.id), snapshot.take())
+                        {
+                            let (requests, counted_size) = (sess.requests, sess.counted_size);
+                            *sess = snapshot;
+                            sess.requests = requests;
+                            sess.counted_size = counted_size;
+                        }
 
-            // Sets a clone of the Store in the Extensions for Direct usage and sets the Session for Direct usage
-            //req.extensions_mut().insert(store.clone());
-            req.extensions_mut().insert(session.clone());
+                        session.store.memory_recount(&session.id);
 
-            let mut response = ready_inner.call(req).await?;
+                        refused = true;
+                        response = errors.respond(
+                            SessionError::SessionLimitReached,
+                            "owner has reached their session limit",
+                        )?;
+                    }
+                    Err(err) => {
+                        if let Some(res) =
+                            errors.respond_after(err, "failed to enforce the owners session limit")
+                        {
+                            return res;
+                        }
+                    }
+                }
+            }
 
             let (renew, storable, destroy, loaded) =
                 if let Some(session_data) = session.store.inner.get(&session.id) {
@@ -233,81 +492,156 @@ where
                 storable = storable,
                 destroy = destroy,
                 loaded = loaded,
-                "Session id: {}",
-                session.id
+                session = %LogId(&session.id),
+                "Session request finished."
             );
 
-            if !destroy && (!session.store.config.session_mode.is_manual() || loaded) && renew {
-                // Lets change the Session ID and destory the old Session from the database.
-                let session_id = match Session::generate_id(&session.store).await {
-                    Ok(v) => v,
-                    Err(err) => {
-                        return trace_error(err, "failed to Generate Session ID");
-                    }
-                };
-
-                // Lets remove it from the database first.
-                if session.store.is_persistent() {
-                    if let Err(err) = session
-                        .store
-                        .database_remove_session(session.id.clone())
-                        .await
-                    {
-                        return trace_error(err, "failed to remove session from database");
-                    };
-                }
-
-                //lets remove it from the filter. if the bottom fails just means it did not exist or was already unloaded.
-                #[cfg(feature = "key-store")]
-                if session.store.config.memory.use_bloom_filters {
-                    let mut filter = session.store.filter.write().await;
-                    filter.remove(session.id.as_bytes());
-                }
+            if !destroy
+                && !refused
+                && (!session.store.config.session_mode.is_manual() || loaded)
+                && renew
+            {
+                let span = tracing::info_span!("session_renew", session = %LogId(&session.id));
 
-                // Lets remove update and reinsert.
-                if let Some((_, mut session_data)) = session.store.inner.remove(&session.id) {
-                    session_data.id = session_id.clone();
-                    session_data.renew = false;
-                    session.id = session_id.clone();
-                    session.store.inner.insert(session.id.clone(), session_data);
+                if let Err(err) = renew_session(&mut session).instrument(span).await {
+                    if let Some(res) = errors.respond_after(err, "failed to renew session") {
+                        return res;
+                    }
                 }
             }
 
             // Add the Session ID so it can link back to a Session if one exists.
             if (!session.store.config.session_mode.is_opt_in() || storable)
                 && session.store.is_persistent()
+                && !session.store.is_cookie_store()
                 && !destroy
+                && !refused
             {
-                let clone_session = if let Some(mut sess) =
-                    session.store.inner.get_mut(&session.id.clone())
-                {
-                    // Check if Database needs to be updated or not. TODO: Make updatable based on a timer for in memory only.
-                    if session.store.config.database.always_save || sess.update || !sess.expired() {
-                        if sess.longterm {
-                            sess.expires = Utc::now() + session.store.config.max_lifespan;
-                        } else {
-                            sess.expires = Utc::now() + session.store.config.lifespan;
-                        };
+                let touch_interval = session.store.config.database.touch_interval;
+                let mut touch = None;
 
-                        sess.update = false;
+                let clone_session =
+                    if let Some(mut sess) = session.store.inner.get_mut(&session.id.clone()) {
+                        // Check if Database needs to be updated or not. TODO: Make updatable based on a timer for in memory only.
+                        if session.store.config.database.always_save
+                            || sess.update
+                            || (touch_interval.is_none() && !sess.expired())
+                        {
+                            sess.expires = sess.next_expiry(&session.store.config);
+                            sess.touched = Utc::now();
+                            sess.update = false;
 
-                        Some(sess.clone())
+                            let clone = sess.clone();
+                            sess.reset_changes();
+                            Some(clone)
+                        } else if touch_interval
+                            .is_some_and(|interval| sess.touched + interval <= Utc::now())
+                        {
+                            sess.expires = sess.next_expiry(&session.store.config);
+                            sess.touched = Utc::now();
+
+                            // Owned sessions are listed as devices so their `last_seen` is stored once
+                            // the touch interval passed. Others only extend their expiry.
+                            if sess.owner.is_some() {
+                                let mut clone = sess.clone();
+                                clone.full_write = true;
+                                sess.reset_changes();
+                                Some(clone)
+                            } else {
+                                touch = Some(sess.expires.timestamp());
+                                None
+                            }
+                        } else {
+                            None
+                        }
                     } else {
                         None
+                    };
+
+                let clone_session = match touch {
+                    Some(expires) => {
+                        match session.store.touch_session(&session.id, expires).await {
+                            Ok(true) => None,
+                            // It was never stored or the database can not touch it so lets store all of it.
+                            Ok(false) => {
+                                session.store.inner.get_mut(&session.id).map(|mut sess| {
+                                    let mut clone = sess.clone();
+                                    clone.full_write = true;
+                                    sess.reset_changes();
+                                    clone
+                                })
+                            }
+                            Err(err) => {
+                                if let Some(res) =
+                                    errors.respond_after(err, "failed to touch session in database")
+                                {
+                                    return res;
+                                }
+
+                                None
+                            }
+                        }
                     }
-                } else {
-                    None
+                    None => clone_session,
                 };
 
                 if let Some(sess) = clone_session {
-                    if let Err(err) = session.store.store_session(&sess).await {
-                        return trace_error(err, "failed to save session to database");
-                    } else {
-                        tracing::info!("Session id {}: was saved to the database.", session.id);
+                    let span = tracing::info_span!("session_store", session = %LogId(&session.id));
+
+                    match session
+                        .store
+                        .save_session(sess, original.as_ref())
+                        .instrument(span)
+                        .await
+                    {
+                        Ok(()) => logging::log(
+                            session.store.config.log_level,
+                            Some(&session.id),
+                            "Session was saved to the database.",
+                        ),
+                        Err(err) => {
+                            if let Some(res) =
+                                errors.respond_after(err, "failed to save session to database")
+                            {
+                                return res;
+                            }
+                        }
                     }
                 }
             }
 
+            // Encrypt the Session into the cookies before anything unloads it from memory.
+            let mut keep_cookies = false;
+            let cookie_value = if session.store.is_cookie_store()
+                && (!session.store.config.session_mode.is_opt_in() || storable)
+                && !destroy
+            {
+                let sess = session
+                    .store
+                    .inner
+                    .get(&session.id)
+                    .map(|sess| sess.clone());
+
+                match sess.map(|sess| session.store.serialize_cookie_session(sess)) {
+                    Some(Ok(value)) => Some(value),
+                    Some(Err(err)) => {
+                        // The request still has to be unloaded so the error replaces the response.
+                        if let Some(res) =
+                            errors.respond_after(err, "failed to store session within the cookies")
+                        {
+                            response = res?;
+                        }
+
+                        // Leave the client with the cookies it already has.
+                        keep_cookies = true;
+                        None
+                    }
+                    None => None,
+                }
+            } else {
+                None
+            };
+
             //lets tell the system we can unload this request now.
             //If there are still more left the bottom wont unload anything.
             session.remove_request();
@@ -321,7 +655,7 @@ where
                     filter.remove(session.id.as_bytes());
                 }
 
-                let _ = session.store.inner.remove(&session.id);
+                session.store.memory_remove(&session.id);
 
                 if session.store.is_persistent() {
                     if let Err(err) = session
@@ -329,9 +663,17 @@ where
                         .database_remove_session(session.id.clone())
                         .await
                     {
-                        return trace_error(err, "failed to remove session from database");
+                        if let Some(res) =
+                            errors.respond_after(err, "failed to remove session from database")
+                        {
+                            return res;
+                        }
                     }
                 }
+
+                if destroy {
+                    session.store.notify_destroy(&session.id).await;
+                }
             }
 
             // We will Deleted the data in memory as it should be stored in the database instead.
@@ -343,17 +685,45 @@ where
                     filter.remove(session.id.as_bytes());
                 }
 
-                session.store.inner.remove(&session.id);
+                session.store.memory_remove(&session.id);
+            }
+
+            // The cookies are the only place the Session is stored so it is unloaded from memory.
+            if session.store.is_cookie_store() {
+                if !session.is_parallel() {
+                    session.store.memory_remove(&session.id);
+                }
+
+                if !keep_cookies {
+                    set_cookie_session(
+                        &session.store.config,
+                        response.headers_mut(),
+                        cookie_value.as_deref(),
+                        cookie_chunks,
+                    );
+                }
             }
 
             set_headers(
                 &session,
+                transport,
                 response.headers_mut(),
                 &ip_user_agent,
                 destroy,
                 storable,
             );
 
+            // Let a `SessionToken` response know the final token now that the ID can not change.
+            if let Some(slot) = response.extensions().get::<TokenSlot>() {
+                let token = (!destroy).then(|| session_token(&session, &ip_user_agent));
+                let _ = slot.0.set(token);
+            }
+
+            session_metrics::record_duration(
+                session_metrics::REQUEST_DURATION,
+                &session.store.config.database.table_name,
+                start,
+            );
             Ok(response)
         })
     }
@@ -367,7 +737,404 @@ where
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("SessionService")
             .field("session_store", &self.session_store)
+            .field("transport", &self.transport)
+            .field("error_handler", &self.error_handler.is_some())
+            .field("keep_response_on_error", &self.keep_response_on_error)
             .field("inner", &self.inner)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        databases::mock::MockPool, DatabaseFailurePolicy, Key, MemoryCapacity, OwnerLimitPolicy,
+        Session, SessionConfig, SessionCookiePool, SessionLayer, SessionNullPool, SessionStore,
+        SessionTransport,
+    };
+    use axum::{body::Body, response::Response, routing::get, Router};
+    use chrono::Duration;
+    use http::{header::SET_COOKIE, Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn call(app: &Router, uri: &str, cookies: &[String]) -> Response {
+        let mut req = Request::get(uri);
+
+        if !cookies.is_empty() {
+            req = req.header("cookie", cookies.join("; "));
+        }
+
+        app.clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    /// The `name=value` pairs of the cookies set by the response.
+    fn set_cookies(res: &Response) -> Vec<String> {
+        res.headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok()?.split(';').next())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    async fn body(res: Response) -> String {
+        let bytes = res.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    async fn cookie_app() -> (Router, SessionStore<SessionCookiePool>) {
+        let config = SessionConfig::default().with_key(Key::generate());
+        let store = SessionStore::new(Some(SessionCookiePool), config)
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route(
+                "/set",
+                get(|session: Session<SessionCookiePool>| async move {
+                    session.set("value", "older");
+                    session.get_session_id()
+                }),
+            )
+            .route(
+                "/large",
+                get(|session: Session<SessionCookiePool>| async move {
+                    session.set("value", "x".repeat(8192));
+                }),
+            )
+            .route(
+                "/get",
+                get(|session: Session<SessionCookiePool>| async move {
+                    session.get::<String>("value").unwrap_or_default()
+                }),
+            )
+            .layer(SessionLayer::new(store.clone()));
+
+        (app, store)
+    }
+
+    #[tokio::test]
+    async fn oversized_cookie_session_keeps_cookies() {
+        let (app, store) = cookie_app().await;
+        let res = call(&app, "/set", &[]).await;
+        let cookies = set_cookies(&res);
+        assert!(cookies
+            .iter()
+            .any(|cookie| cookie.starts_with("session.0=")));
+
+        let res = call(&app, "/large", &cookies).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!set_cookies(&res)
+            .iter()
+            .any(|cookie| cookie.starts_with("session.")));
+
+        assert!(store.inner.is_empty());
+
+        let res = call(&app, "/get", &cookies).await;
+        assert_eq!(body(res).await, "older");
+    }
+
+    #[tokio::test]
+    async fn cookie_session_keeps_in_flight_copy() {
+        let (app, store) = cookie_app().await;
+        let res = call(&app, "/set", &[]).await;
+        let cookies = set_cookies(&res);
+        let id = body(res).await;
+
+        // Another request is still using a newer copy of the Session.
+        let mut in_flight = crate::SessionData::new(id.clone(), true, &store.config);
+        in_flight.set("value", "newer");
+        store.inner.insert(id.clone(), in_flight);
+
+        let res = call(&app, "/get", &cookies).await;
+        assert_eq!(body(res).await, "newer");
+        assert_eq!(store.inner.get(&id).unwrap().requests, 1);
+    }
+
+    #[tokio::test]
+    async fn cookie_then_header_echoes_the_used_transport() {
+        let config = SessionConfig::default().with_transport(SessionTransport::CookieThenHeader);
+        let store = SessionStore::new(Some(SessionNullPool), config)
+            .await
+            .unwrap();
+        let app = Router::new()
+            .route("/", get(|_: Session<SessionNullPool>| async {}))
+            .layer(SessionLayer::new(store));
+
+        // A new client could use either so both are set.
+        let res = call(&app, "/", &[]).await;
+        let token = res.headers().get("session").cloned().unwrap();
+        let cookies = set_cookies(&res);
+        assert!(cookies.iter().any(|cookie| cookie.starts_with("session=")));
+
+        let res = call(&app, "/", &cookies).await;
+        assert!(res.headers().get("session").is_none());
+        assert!(set_cookies(&res)
+            .iter()
+            .any(|cookie| cookie.starts_with("session=")));
+
+        let req = Request::get("/")
+            .header("session", token.clone())
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.headers().get("session"), Some(&token));
+        assert!(set_cookies(&res).is_empty());
+    }
+
+    #[tokio::test]
+    async fn degraded_requests_are_served_from_memory() {
+        let pool = MockPool::default();
+        let config = SessionConfig::default()
+            .with_failure_policy(DatabaseFailurePolicy::ServeFromMemory(Duration::zero()));
+        let store = SessionStore::new(Some(pool.clone()), config).await.unwrap();
+        let app = Router::new()
+            .route(
+                "/set",
+                get(|session: Session<MockPool>| async move {
+                    session.set("value", "memory");
+                    session.get_session_id()
+                }),
+            )
+            .route(
+                "/get",
+                get(|session: Session<MockPool>| async move {
+                    session.get::<String>("value").unwrap_or_default()
+                }),
+            )
+            .layer(SessionLayer::new(store.clone()));
+
+        pool.set_failing(true);
+        let res = call(&app, "/set", &[]).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let cookies = set_cookies(&res);
+        let id = body(res).await;
+        assert!(store.is_degraded());
+        assert_eq!(store.pending_writes(), 1);
+
+        let res = call(&app, "/get", &cookies).await;
+        assert_eq!(body(res).await, "memory");
+
+        // The next request replays the queued write once the database is back.
+        pool.set_failing(false);
+        let res = call(&app, "/get", &cookies).await;
+        assert_eq!(body(res).await, "memory");
+        assert!(!store.is_degraded());
+        assert_eq!(store.pending_writes(), 0);
+        assert!(pool.row(&id).is_some());
+    }
+
+    async fn mock_app(pool: &MockPool, config: SessionConfig) -> (Router, SessionStore<MockPool>) {
+        let store = SessionStore::new(Some(pool.clone()), config).await.unwrap();
+        let app = Router::new()
+            .route(
+                "/set",
+                get(|session: Session<MockPool>| async move {
+                    session.set("value", "stored");
+                    session.get_session_id()
+                }),
+            )
+            .route(
+                "/get",
+                get(|session: Session<MockPool>| async move {
+                    session.get::<String>("value").unwrap_or_default()
+                }),
+            )
+            .route(
+                "/own",
+                get(|session: Session<MockPool>| async move {
+                    session.set_owner("user");
+                    session.get_session_id()
+                }),
+            )
+            .route(
+                "/login",
+                get(|session: Session<MockPool>| async move {
+                    session.login("user");
+                }),
+            )
+            .layer(SessionLayer::new(store.clone()));
+
+        (app, store)
+    }
+
+    #[tokio::test]
+    async fn touch_only_extends_expiry() {
+        let pool = MockPool::default();
+        let config = SessionConfig::default().with_touch_interval(Duration::zero());
+        let (app, store) = mock_app(&pool, config).await;
+
+        let res = call(&app, "/set", &[]).await;
+        let cookies = set_cookies(&res);
+        let id = body(res).await;
+        assert_eq!(pool.take_writes(), vec![format!("store:{id}")]);
+
+        pool.state().touches = true;
+        pool.state().rows.get_mut(&id).unwrap().expires -= 60;
+        let before = pool.row(&id).unwrap();
+
+        call(&app, "/get", &cookies).await;
+        assert_eq!(pool.take_writes(), vec![format!("touch:{id}")]);
+
+        let after = pool.row(&id).unwrap();
+        assert_eq!(after.session, before.session);
+        assert_eq!(
+            after.expires,
+            store.inner.get(&id).unwrap().expires.timestamp()
+        );
+        assert!(after.expires > before.expires);
+    }
+
+    #[tokio::test]
+    async fn touch_falls_back_to_store() {
+        let pool = MockPool::default();
+        let config = SessionConfig::default().with_touch_interval(Duration::zero());
+        let (app, _) = mock_app(&pool, config).await;
+
+        let res = call(&app, "/set", &[]).await;
+        let cookies = set_cookies(&res);
+        let id = body(res).await;
+        pool.take_writes();
+
+        // The mock's `touch` returns false like databases that can not touch a Session.
+        call(&app, "/get", &cookies).await;
+        assert_eq!(pool.take_writes(), vec![format!("store:{id}")]);
+    }
+
+    #[tokio::test]
+    async fn owned_sessions_store_last_seen() {
+        let pool = MockPool::default();
+        let config = SessionConfig::default().with_touch_interval(Duration::zero());
+        let (app, store) = mock_app(&pool, config).await;
+        pool.state().touches = true;
+
+        let res = call(&app, "/own", &[]).await;
+        let cookies = set_cookies(&res);
+        let id = body(res).await;
+        pool.take_writes();
+        let stored = store.load_session(id.clone()).await.unwrap().unwrap();
+
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        call(&app, "/get", &cookies).await;
+        assert_eq!(pool.take_writes(), vec![format!("store:{id}")]);
+
+        let seen = store.load_session(id).await.unwrap().unwrap();
+        assert!(seen.last_seen > stored.last_seen);
+    }
+
+    /// Gives a new Session to the owner and returns its ID, waiting so each is created after the last.
+    async fn own_new(app: &Router) -> (StatusCode, String) {
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let res = call(app, "/own", &[]).await;
+        (res.status(), body(res).await)
+    }
+
+    #[tokio::test]
+    async fn owner_limit_evicts_oldest() {
+        let pool = MockPool::default();
+        let config =
+            SessionConfig::default().with_max_sessions_per_owner(2, OwnerLimitPolicy::EvictOldest);
+        let (app, store) = mock_app(&pool, config).await;
+
+        let (_, oldest) = own_new(&app).await;
+        let (_, older) = own_new(&app).await;
+        let (status, newest) = own_new(&app).await;
+        assert_eq!(status, StatusCode::OK);
+
+        assert!(pool.row(&oldest).is_none());
+        assert!(!store.inner.contains_key(&oldest));
+        assert!(pool.row(&older).is_some());
+        assert_eq!(pool.row(&newest).unwrap().owner.as_deref(), Some("user"));
+    }
+
+    #[tokio::test]
+    async fn owner_limit_refuses_new() {
+        let pool = MockPool::default();
+        let config =
+            SessionConfig::default().with_max_sessions_per_owner(1, OwnerLimitPolicy::RefuseNew);
+        let (app, store) = mock_app(&pool, config).await;
+
+        let (_, first) = own_new(&app).await;
+        let (status, _) = own_new(&app).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let refused = store
+            .inner
+            .iter()
+            .map(|r| r.key().clone())
+            .find(|id| *id != first)
+            .unwrap();
+
+        assert!(pool.row(&refused).is_none());
+        assert!(store.inner.get(&refused).unwrap().owner.is_none());
+        assert_eq!(store.list_by_owner("user").await.unwrap(), vec![first]);
+    }
+
+    #[tokio::test]
+    async fn refused_login_keeps_prior_data() {
+        let pool = MockPool::default();
+        let config = SessionConfig::default()
+            .with_max_sessions_per_owner(1, OwnerLimitPolicy::RefuseNew)
+            .with_clear_on_login(true);
+        let (app, _store) = mock_app(&pool, config).await;
+        own_new(&app).await;
+
+        let res = call(&app, "/set", &[]).await;
+        let cookies = set_cookies(&res);
+        let res = call(&app, "/login", &cookies).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let res = call(&app, "/get", &cookies).await;
+        assert_eq!(body(res).await, "stored");
+    }
+
+    #[tokio::test]
+    async fn evicted_sessions_reload_from_database() {
+        let pool = MockPool::default();
+        let config = SessionConfig::default().with_memory_capacity(MemoryCapacity::Entries(1));
+        let (app, store) = mock_app(&pool, config).await;
+
+        let res = call(&app, "/set", &[]).await;
+        let cookies = set_cookies(&res);
+        let id = body(res).await;
+
+        // Loading another Session pushes the first out of memory.
+        call(&app, "/get", &[]).await;
+        assert!(!store.inner.contains_key(&id));
+
+        let res = call(&app, "/get", &cookies).await;
+        assert_eq!(body(res).await, "stored");
+        assert!(store.inner.contains_key(&id));
+    }
+
+    #[tokio::test]
+    async fn degraded_recreated_sessions_keep_stored_row() {
+        let pool = MockPool::default();
+        let config = SessionConfig::default()
+            .with_failure_policy(DatabaseFailurePolicy::ServeFromMemory(Duration::zero()));
+        let (app, store) = mock_app(&pool, config).await;
+
+        let res = call(&app, "/set", &[]).await;
+        let cookies = set_cookies(&res);
+        let id = body(res).await;
+        store.memory_remove(&id);
+        let before = pool.row(&id).unwrap();
+
+        // It can not be loaded during the outage so it starts over empty.
+        pool.set_failing(true);
+        let res = call(&app, "/get", &cookies).await;
+        assert_eq!(body(res).await, "");
+        assert!(store.is_degraded());
+
+        pool.set_failing(false);
+        call(&app, "/get", &[]).await;
+        assert!(!store.is_degraded());
+        assert_eq!(pool.row(&id).unwrap().session, before.session);
+
+        let res = call(&app, "/get", &cookies).await;
+        assert_eq!(body(res).await, "stored");
+    }
+}
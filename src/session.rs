@@ -1,5 +1,9 @@
-use crate::{DatabasePool, SessionData, SessionError, SessionStore};
+use crate::{
+    sec::csrf, session_metrics, DatabasePool, FlashLevel, FlashMessage, SessionData, SessionError,
+    SessionKey, SessionMetadata, SessionStore,
+};
 use axum::extract::FromRequestParts;
+use chrono::{DateTime, Utc};
 
 #[cfg(feature = "key-store")]
 use fastbloom_rs::Membership;
@@ -73,6 +77,8 @@ where
 
     #[cfg(feature = "key-store")]
     pub(crate) async fn generate_id(store: &SessionStore<S>) -> Result<String, SessionError> {
+        let table = &store.config.database.table_name;
+
         loop {
             let token = store.config.id_generator.generate();
 
@@ -80,48 +86,55 @@ where
                 && !store.inner.contains_key(&token)
             {
                 //This fixes an already used but in database issue.
-                if let Some(client) = &store.client {
+                if store.client.is_some() {
                     // Unwrap should be safe to use as we would want it to crash if there was a major database error.
                     // This would mean the database no longer is online or the table missing etc.
-                    if !client
-                        .exists(&token.to_string(), &store.config.database.table_name)
-                        .await?
-                    {
+                    if !store.exists_in_database(&token).await? {
+                        session_metrics::increment(session_metrics::IDS_GENERATED, table, 1);
                         return Ok(token);
                     }
+
+                    session_metrics::increment(session_metrics::ID_COLLISIONS, table, 1);
                 } else {
+                    session_metrics::increment(session_metrics::IDS_GENERATED, table, 1);
                     return Ok(token);
                 }
             } else {
                 let filter = store.filter.read().await;
 
                 if !filter.contains(token.to_string().as_bytes()) {
+                    session_metrics::increment(session_metrics::IDS_GENERATED, table, 1);
                     return Ok(token);
                 }
+
+                session_metrics::increment(session_metrics::BLOOM_REJECTIONS, table, 1);
             }
         }
     }
 
     #[cfg(not(feature = "key-store"))]
     pub(crate) async fn generate_id(store: &SessionStore<S>) -> Result<String, SessionError> {
+        let table = &store.config.database.table_name;
+
         loop {
             let token = store.config.id_generator.generate();
 
             if !store.inner.contains_key(&token) {
                 //This fixes an already used but in database issue.
-                if let Some(client) = &store.client {
+                if store.client.is_some() {
                     // Unwrap should be safe to use as we would want it to crash if there was a major database error.
                     // This would mean the database no longer is online or the table missing etc.
-                    if !client
-                        .exists(&token.to_string(), &store.config.database.table_name)
-                        .await?
-                    {
+                    if !store.exists_in_database(&token).await? {
+                        session_metrics::increment(session_metrics::IDS_GENERATED, table, 1);
                         return Ok(token);
                     }
                 } else {
+                    session_metrics::increment(session_metrics::IDS_GENERATED, table, 1);
                     return Ok(token);
                 }
             }
+
+            session_metrics::increment(session_metrics::ID_COLLISIONS, table, 1);
         }
     }
     /// Sets the Session to create the SessionData based on the current Session ID.
@@ -142,7 +155,7 @@ where
             );
         }
         let session_data = SessionData::new(self.id.clone(), true, &self.store.config);
-        self.store.inner.insert(self.id.clone(), session_data);
+        self.store.memory_insert(session_data);
     }
 
     /// Checks if the SessionData was created or not.
@@ -293,6 +306,203 @@ where
         self.store.remove(self.id.clone(), key);
     }
 
+    /// Gets data from the Session's HashMap using a typed [`SessionKey`].
+    ///
+    /// Provides an `Option<K::Value>` that returns the requested data from the Sessions store.
+    /// Returns None if Key does not exist or if serde_json failed to deserialize.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let id = session.get_typed::<UserId>().unwrap_or(0);
+    /// ```
+    ///
+    #[inline]
+    pub fn get_typed<K: SessionKey>(&self) -> Option<K::Value> {
+        self.store.get(self.id.clone(), K::NAME)
+    }
+
+    /// Sets data to the Current Session's HashMap using a typed [`SessionKey`].
+    /// This will also update the database on Response Phase.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.set_typed::<UserId>(1);
+    /// ```
+    ///
+    #[inline]
+    pub fn set_typed<K: SessionKey>(&self, value: K::Value) {
+        self.store.set(self.id.clone(), K::NAME, value);
+    }
+
+    /// Removes a typed [`SessionKey`] from the Current Session's HashMap returning it.
+    /// This will also update the database on Response Phase.
+    ///
+    /// Returns None if Key does not exist or if serde_json failed to deserialize.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let id = session.remove_typed::<UserId>();
+    /// ```
+    ///
+    #[inline]
+    pub fn remove_typed<K: SessionKey>(&self) -> Option<K::Value> {
+        self.store.get_remove(self.id.clone(), K::NAME)
+    }
+
+    /// Sets the user or account that owns the Current Session.
+    /// This allows it to be found with `SessionStore::list_by_owner` and destroyed
+    /// with `SessionStore::destroy_all_for_owner`.
+    /// This will also update the database on Response Phase.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.set_owner(user.id);
+    /// ```
+    ///
+    #[inline]
+    pub fn set_owner(&self, owner: impl ToString) {
+        self.store
+            .set_owner(self.id.clone(), Some(owner.to_string()));
+    }
+
+    /// Removes the owner of the Current Session, like when the user logs out.
+    /// This will also update the database on Response Phase.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.remove_owner();
+    /// ```
+    ///
+    #[inline]
+    pub fn remove_owner(&self) {
+        self.store.set_owner(self.id.clone(), None);
+    }
+
+    /// Gets the owner of the Current Session if one was set.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let owner = session.get_owner();
+    /// ```
+    ///
+    #[inline]
+    pub fn get_owner(&self) -> Option<String> {
+        self.store
+            .inner
+            .get(&self.id)
+            .and_then(|instance| instance.owner.clone())
+    }
+
+    /// Logs the principal, like a user ID, into the Current Session.
+    /// This sets it as the owner, records when they authenticated and renews the Session ID
+    /// to prevent session fixation. If `SessionConfig::with_clear_on_login` is enabled the
+    /// data set before the login is cleared.
+    /// This will also update the database on Response Phase.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.login(user.id);
+    /// ```
+    ///
+    #[inline]
+    pub fn login(&self, principal: impl ToString) {
+        self.store.login(self.id.clone(), principal.to_string());
+    }
+
+    /// Logs the principal out of the Current Session.
+    /// This removes the owner, the authentication time and all data then renews the Session ID.
+    /// This will also update the database on Response Phase.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.logout();
+    /// ```
+    ///
+    #[inline]
+    pub fn logout(&self) {
+        self.store.logout(self.id.clone());
+    }
+
+    /// Gets when the principal last logged in using `login`.
+    /// Returns None if no one is logged into the Current Session.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let authenticated_at = session.get_authenticated_at();
+    /// ```
+    ///
+    #[inline]
+    pub fn get_authenticated_at(&self) -> Option<DateTime<Utc>> {
+        self.store
+            .inner
+            .get(&self.id)
+            .and_then(|instance| instance.authenticated_at)
+    }
+
+    /// Gets the metadata of the Current Session, like when it was created and the client using it.
+    /// Returns None if the Session has no data, like in Manual mode before `create_data`.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// if let Some(metadata) = session.metadata() {
+    ///     println!("Signed in since {}", metadata.created_at);
+    /// }
+    /// ```
+    ///
+    #[inline]
+    pub fn metadata(&self) -> Option<SessionMetadata> {
+        self.store
+            .inner
+            .get(&self.id)
+            .map(|instance| instance.metadata())
+    }
+
+    /// Queues a Flash Message to be shown once on a later request.
+    /// Multiple messages can be queued and are kept in order until taken.
+    /// This will also update the database on Response Phase.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.flash(FlashLevel::Success, "Your profile was saved.");
+    /// ```
+    ///
+    #[inline]
+    pub fn flash(&self, level: FlashLevel, message: impl Into<String>) {
+        self.store.flash(self.id.clone(), level, message.into());
+    }
+
+    /// Removes all queued Flash Messages from the Current Session returning them.
+    /// The [`Flashes`](crate::Flashes) extractor can be used to do this within a handlers arguments.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// for flash in session.take_flashes() {
+    ///     println!("{}: {}", flash.level, flash.message);
+    /// }
+    /// ```
+    ///
+    #[inline]
+    pub fn take_flashes(&self) -> Vec<FlashMessage> {
+        self.store.take_flashes(self.id.clone())
+    }
+
+    /// Returns a newly masked CSRF token for use with the [`CsrfLayer`](crate::CsrfLayer).
+    /// The token is created if the Session does not have one yet.
+    ///
+    /// Use this instead of the [`CsrfToken`](crate::CsrfToken) extractor after calling `renew`
+    /// as renewing the Session also rotates the token.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.renew();
+    /// let token = session.csrf_token();
+    /// ```
+    ///
+    #[inline]
+    pub fn csrf_token(&self) -> String {
+        csrf::mask(&self.store.csrf_token(self.id.clone()))
+    }
+
     /// Clears all data from the Current Session's HashMap instantly.
     /// This will also update the database on Response Phase.
     ///
@@ -563,6 +773,21 @@ where
         self.store.get(self.id.clone(), key)
     }
 
+    /// Gets data from the Session's HashMap using a typed [`SessionKey`].
+    ///
+    /// Provides an `Option<K::Value>` that returns the requested data from the Sessions store.
+    /// Returns None if Key does not exist or if serde_json failed to deserialize.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let id = session.get_typed::<UserId>().unwrap_or(0);
+    /// ```
+    ///
+    #[inline]
+    pub fn get_typed<K: SessionKey>(&self) -> Option<K::Value> {
+        self.store.get(self.id.clone(), K::NAME)
+    }
+
     /// Returns a i64 count of how many Sessions exist.
     ///
     /// If the Session is persistent it will return all sessions within the database.
@@ -1,7 +1,16 @@
-use crate::SessionConfig;
+use crate::{
+    flash::{FlashLevel, FlashMessage, FLASH_KEY},
+    metadata::coarse_ip,
+    sec::csrf,
+    SessionConfig, SessionMetadata, SessionPatch, SessionUserAgent,
+};
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    net::IpAddr,
+};
 
 /// The Store and Configured Data for a Session.
 ///
@@ -35,6 +44,52 @@ pub struct SessionData {
     pub(crate) update: bool,
     #[serde(skip)]
     pub(crate) requests: usize,
+    /// The version last stored within the database. 0 if it was never stored.
+    #[serde(default)]
+    pub(crate) version: i64,
+    /// The CSRF token used by the `CsrfLayer`. None until it is first needed.
+    #[serde(default)]
+    pub(crate) csrf_token: Option<String>,
+    /// The user or account that owns this session.
+    #[serde(default)]
+    pub(crate) owner: Option<String>,
+    /// If the owner needs to be stored within the databases owner index.
+    #[serde(skip)]
+    pub(crate) owner_changed: bool,
+    /// When the owner last logged in using `Session::login`.
+    #[serde(default)]
+    pub(crate) authenticated_at: Option<DateTime<Utc>>,
+    /// Keys set since the session was last stored.
+    #[serde(skip)]
+    pub(crate) changed_keys: HashSet<String>,
+    /// Keys removed since the session was last stored.
+    #[serde(skip)]
+    pub(crate) removed_keys: HashSet<String>,
+    /// Set when more than the data keys changed so the whole session must be stored.
+    #[serde(skip)]
+    pub(crate) full_write: bool,
+    /// When the session was first created. Sessions stored before this existed start from when they are loaded.
+    #[serde(default = "Utc::now")]
+    pub(crate) created_at: DateTime<Utc>,
+    /// When the session was last used by a request.
+    #[serde(default = "Utc::now")]
+    pub(crate) last_seen: DateTime<Utc>,
+    /// The clients IP with the host part removed.
+    #[serde(default)]
+    pub(crate) ip: Option<String>,
+    /// The clients browser and operating system.
+    #[serde(default)]
+    pub(crate) user_agent: Option<SessionUserAgent>,
+    /// When the expiry was last extended within the database.
+    #[serde(skip)]
+    pub(crate) touched: DateTime<Utc>,
+    /// The `memory_size` counted within the stores memory total while in memory.
+    #[serde(skip)]
+    pub(crate) counted_size: usize,
+    /// Created again because the database could not be read, so it is only stored
+    /// if the database does not already have a Session with this ID.
+    #[serde(skip)]
+    pub(crate) recreated: bool,
 }
 
 impl SessionData {
@@ -63,6 +118,22 @@ impl SessionData {
             store: storable,
             update: true,
             requests: 1,
+            version: 0,
+            csrf_token: None,
+            owner: None,
+            owner_changed: false,
+            authenticated_at: None,
+            changed_keys: HashSet::new(),
+            removed_keys: HashSet::new(),
+            // It was never stored so all of it needs to be.
+            full_write: true,
+            created_at: Utc::now(),
+            last_seen: Utc::now(),
+            ip: None,
+            user_agent: None,
+            touched: Utc::now(),
+            counted_size: 0,
+            recreated: false,
         }
     }
 
@@ -84,6 +155,30 @@ impl SessionData {
         self.expires < Utc::now()
     }
 
+    /// Determines whether the session has existed longer than the absolute lifespan.
+    #[inline]
+    pub(crate) fn past_absolute_lifespan(&self, config: &SessionConfig) -> bool {
+        config
+            .absolute_lifespan
+            .is_some_and(|lifespan| self.created_at + lifespan <= Utc::now())
+    }
+
+    /// Returns when the session expires if it is used now, never going past its absolute lifespan.
+    #[inline]
+    pub(crate) fn next_expiry(&self, config: &SessionConfig) -> DateTime<Utc> {
+        let expires = Utc::now()
+            + if self.longterm {
+                config.max_lifespan
+            } else {
+                config.lifespan
+            };
+
+        match config.absolute_lifespan {
+            Some(absolute) => expires.min(self.created_at + absolute),
+            None => expires,
+        }
+    }
+
     /// Validates and checks if the Session is to be destroyed.
     /// If so the Sessions Data is Cleared.
     /// autoremove is then updated for the session regardless.
@@ -106,6 +201,7 @@ impl SessionData {
 
             if self.expired() {
                 self.data.clear();
+                self.full_write = true;
             }
         }
 
@@ -127,6 +223,33 @@ impl SessionData {
     pub fn renew(&mut self) {
         self.renew = true;
         self.update = true;
+        self.full_write = true;
+
+        // Rotate the CSRF token so one leaked before the renew can not be used after it.
+        if self.csrf_token.is_some() {
+            self.csrf_token = Some(csrf::generate_token());
+        }
+    }
+
+    /// Returns the CSRF token creating one if it does not exist yet.
+    /// This will update the database on Response Phase if it was created.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let token = session_data.csrf_token();
+    /// ```
+    ///
+    #[inline]
+    pub(crate) fn csrf_token(&mut self) -> String {
+        if let Some(token) = &self.csrf_token {
+            return token.clone();
+        }
+
+        let token = csrf::generate_token();
+        self.csrf_token = Some(token.clone());
+        self.update = true;
+        self.full_write = true;
+        token
     }
 
     /// Sets the Session to force update the database.
@@ -168,6 +291,7 @@ impl SessionData {
     pub fn set_longterm(&mut self, longterm: bool) {
         self.longterm = longterm;
         self.update = true;
+        self.full_write = true;
     }
 
     /// Sets the Current Session to be storable.
@@ -223,6 +347,7 @@ impl SessionData {
     pub fn get_remove<T: serde::de::DeserializeOwned>(&mut self, key: &str) -> Option<T> {
         let string = self.data.remove(key)?;
         self.update = true;
+        self.mark_removed(key);
         serde_json::from_str(&string).ok()
     }
 
@@ -239,6 +364,8 @@ impl SessionData {
         let value = serde_json::to_string(&value).unwrap_or_else(|_| "".to_string());
         let _ = self.data.insert(key.to_string(), value);
         self.update = true;
+        self.removed_keys.remove(key);
+        self.changed_keys.insert(key.to_string());
     }
 
     /// Removes a Key from the Current Session's HashMap.
@@ -254,6 +381,148 @@ impl SessionData {
     pub fn remove(&mut self, key: &str) {
         let _ = self.data.remove(key);
         self.update = true;
+        self.mark_removed(key);
+    }
+
+    #[inline]
+    fn mark_removed(&mut self, key: &str) {
+        self.changed_keys.remove(key);
+        self.removed_keys.insert(key.to_string());
+    }
+
+    /// Sets the owner of the Session so it can be found by `SessionStore::list_by_owner`.
+    /// This will also update the database on Response Phase.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.set_owner(Some("user-1".to_owned()));
+    /// ```
+    ///
+    #[inline]
+    pub(crate) fn set_owner(&mut self, owner: Option<String>) {
+        if self.owner != owner {
+            self.owner = owner;
+            self.owner_changed = true;
+            self.full_write = true;
+        }
+
+        self.update = true;
+    }
+
+    /// Sets the owner and authentication time then renews the Session so an ID
+    /// known before the login can not be used to reach the authenticated Session.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.login("user-1".to_owned(), false);
+    /// ```
+    ///
+    #[inline]
+    pub(crate) fn login(&mut self, principal: String, clear: bool) {
+        if clear {
+            self.clear();
+        }
+
+        self.set_owner(Some(principal));
+        self.authenticated_at = Some(Utc::now());
+        self.renew();
+    }
+
+    /// Removes the owner, authentication time and all data then renews the Session.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.logout();
+    /// ```
+    ///
+    #[inline]
+    pub(crate) fn logout(&mut self) {
+        self.clear();
+        self.set_owner(None);
+        self.authenticated_at = None;
+        self.renew();
+    }
+
+    /// Records that a request used the session from the client.
+    /// A change of IP or user agent will update the database on Response Phase.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session_data.seen(Some(ip), Some("Mozilla/5.0"));
+    /// ```
+    ///
+    #[inline]
+    pub(crate) fn seen(&mut self, ip: Option<IpAddr>, user_agent: Option<&str>) {
+        let ip = ip.map(coarse_ip);
+        let user_agent = user_agent.map(SessionUserAgent::parse);
+
+        if self.ip != ip || self.user_agent != user_agent {
+            self.ip = ip;
+            self.user_agent = user_agent;
+            self.update = true;
+            self.full_write = true;
+        }
+
+        self.last_seen = Utc::now();
+    }
+
+    /// Returns an estimate of how many bytes the session uses within memory.
+    #[inline]
+    pub(crate) fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.id.len()
+            + self
+                .data
+                .iter()
+                .map(|(key, value)| key.len() + value.len())
+                .sum::<usize>()
+    }
+
+    /// Returns the sessions metadata.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let metadata = session_data.metadata();
+    /// ```
+    ///
+    #[inline]
+    pub(crate) fn metadata(&self) -> SessionMetadata {
+        SessionMetadata {
+            id: self.id.clone(),
+            created_at: self.created_at,
+            last_seen: self.last_seen,
+            authenticated_at: self.authenticated_at,
+            ip: self.ip.clone(),
+            user_agent: self.user_agent.clone(),
+        }
+    }
+
+    /// Queues a Flash Message to be taken on a later request.
+    /// This will also update the database on Response Phase.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session.flash(FlashLevel::Success, "Saved!");
+    /// ```
+    ///
+    #[inline]
+    pub(crate) fn flash(&mut self, level: FlashLevel, message: String) {
+        let mut flashes: Vec<FlashMessage> = self.get(FLASH_KEY).unwrap_or_default();
+        flashes.push(FlashMessage { level, message });
+        self.set(FLASH_KEY, flashes);
+    }
+
+    /// Removes all queued Flash Messages returning them in the order they were queued.
+    /// This will also update the database on Response Phase if any existed.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let flashes = session.take_flashes();
+    /// ```
+    ///
+    #[inline]
+    pub(crate) fn take_flashes(&mut self) -> Vec<FlashMessage> {
+        self.get_remove(FLASH_KEY).unwrap_or_default()
     }
 
     /// Clears all data from the Current Session's HashMap.
@@ -268,6 +537,84 @@ impl SessionData {
     pub fn clear(&mut self) {
         self.data.clear();
         self.update = true;
+        self.full_write = true;
+    }
+
+    /// Builds the patch of data keys that changed since the session was last stored.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// let patch = session_data.patch();
+    /// ```
+    ///
+    pub(crate) fn patch(&self) -> SessionPatch {
+        let changed: serde_json::Map<String, serde_json::Value> = self
+            .changed_keys
+            .iter()
+            .filter_map(|key| {
+                self.data
+                    .get(key)
+                    .map(|value| (key.clone(), serde_json::Value::String(value.clone())))
+            })
+            .collect();
+
+        let mut data = changed.clone();
+
+        // A null removes the key within a merge patch.
+        for key in &self.removed_keys {
+            data.insert(key.clone(), serde_json::Value::Null);
+        }
+
+        let mut fields = serde_json::Map::new();
+        fields.insert("last_seen".to_owned(), serde_json::json!(self.last_seen));
+
+        let mut merge_patch = fields.clone();
+        merge_patch.insert("data".to_owned(), serde_json::Value::Object(data));
+
+        SessionPatch {
+            changed: serde_json::Value::Object(changed).to_string(),
+            removed: self.removed_keys.iter().cloned().collect(),
+            fields: serde_json::Value::Object(fields).to_string(),
+            merge_patch: serde_json::Value::Object(merge_patch).to_string(),
+        }
+    }
+
+    /// Forgets the tracked changes once they are being stored.
+    #[inline]
+    pub(crate) fn reset_changes(&mut self) {
+        self.changed_keys.clear();
+        self.removed_keys.clear();
+        self.full_write = false;
+    }
+
+    /// Reapplies the changes made to the data since `original` on top of a newer
+    /// version of the Session that another request stored in the database.
+    /// Keys that were changed are overwritten and keys that were removed are removed.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// session_data.rebase(stored, &original);
+    /// ```
+    ///
+    #[inline]
+    pub(crate) fn rebase(&mut self, stored: SessionData, original: &HashMap<String, String>) {
+        let mut data = stored.data;
+
+        for (key, value) in &self.data {
+            if original.get(key) != Some(value) {
+                data.insert(key.clone(), value.clone());
+            }
+        }
+
+        for key in original.keys() {
+            if !self.data.contains_key(key) {
+                data.remove(key);
+            }
+        }
+
+        self.data = data;
+        self.version = stored.version;
+        self.full_write = true;
     }
 
     /// Removes a Request from the request counter
@@ -319,4 +666,122 @@ impl SessionData {
 pub(crate) struct SessionTimers {
     pub(crate) last_expiry_sweep: DateTime<Utc>,
     pub(crate) last_database_expiry_sweep: DateTime<Utc>,
+    pub(crate) next_database_retry: DateTime<Utc>,
+    pub(crate) next_eviction_scan: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{SessionConfig, SessionData, SessionPatch};
+    use chrono::Duration;
+
+    #[test]
+    fn hash_fields_apply_the_patch() {
+        let config = SessionConfig::default();
+        let mut session = SessionData::new("id".to_owned(), true, &config);
+        session.set("cart", 1);
+        session.set("flash", "hello");
+        let stored = serde_json::to_string(&session).unwrap();
+
+        session.reset_changes();
+        session.set("cart", 2);
+        session.remove("flash");
+        session.last_seen += Duration::minutes(5);
+
+        let (mut set, del) = session.patch().hash_fields();
+        assert!(del.contains(&"set:flash".to_owned()));
+        set.push(("version".to_owned(), "3".to_owned()));
+
+        let loaded: SessionData =
+            serde_json::from_str(&SessionPatch::apply_hash_fields(&stored, set).unwrap()).unwrap();
+        assert_eq!(loaded.get::<i32>("cart"), Some(2));
+        assert_eq!(loaded.get::<String>("flash"), None);
+        assert_eq!(loaded.last_seen, session.last_seen);
+        assert_eq!(loaded.version, 3);
+    }
+
+    #[test]
+    fn rebase_keeps_changes_from_both_sides() {
+        let config = SessionConfig::default();
+        let mut session = SessionData::new("id".to_owned(), true, &config);
+        session.set("cart", 1);
+        session.set("flash", "hello");
+        let original = session.data.clone();
+
+        let mut stored = session.clone();
+        stored.set("cart", 2);
+        stored.set("theme", "dark");
+        stored.version = 4;
+
+        session.set("count", 10);
+        session.remove("flash");
+        session.rebase(stored, &original);
+
+        assert_eq!(session.get::<i32>("cart"), Some(2));
+        assert_eq!(session.get::<String>("theme"), Some("dark".to_owned()));
+        assert_eq!(session.get::<i32>("count"), Some(10));
+        assert_eq!(session.get::<String>("flash"), None);
+        assert_eq!(session.version, 4);
+    }
+
+    #[test]
+    fn absolute_lifespan_caps_expiry() {
+        let config = SessionConfig::default().with_absolute_lifetime(Duration::hours(1));
+        let mut session = SessionData::new("id".to_owned(), true, &config);
+        assert_eq!(
+            session.next_expiry(&config),
+            session.created_at + Duration::hours(1)
+        );
+        assert!(!session.past_absolute_lifespan(&config));
+
+        session.created_at -= Duration::hours(2);
+        assert!(session.past_absolute_lifespan(&config));
+        assert!(!session.past_absolute_lifespan(&SessionConfig::default()));
+    }
+
+    #[test]
+    fn login_renews_and_logout_clears() {
+        let config = SessionConfig::default();
+        let mut session = SessionData::new("id".to_owned(), true, &config);
+        session.set("cart", 1);
+        session.reset_changes();
+
+        session.login("user-1".to_owned(), false);
+        assert!(session.renew);
+        assert!(session.owner_changed);
+        assert!(session.authenticated_at.is_some());
+        assert_eq!(session.owner.as_deref(), Some("user-1"));
+        assert_eq!(session.get::<i32>("cart"), Some(1));
+
+        session.logout();
+        assert_eq!(session.owner, None);
+        assert_eq!(session.authenticated_at, None);
+        assert_eq!(session.get::<i32>("cart"), None);
+    }
+
+    #[test]
+    fn patch_only_contains_changed_keys() {
+        let config = SessionConfig::default();
+        let mut session = SessionData::new("id".to_owned(), true, &config);
+        session.set("kept", 1);
+        session.set("removed", 2);
+        session.reset_changes();
+
+        session.set("changed", "value");
+        session.remove("removed");
+        let patch = session.patch();
+
+        let changed: serde_json::Value = serde_json::from_str(&patch.changed).unwrap();
+        assert_eq!(changed, serde_json::json!({ "changed": "\"value\"" }));
+        assert_eq!(patch.removed, vec!["removed".to_owned()]);
+
+        let merge: serde_json::Value = serde_json::from_str(&patch.merge_patch).unwrap();
+        assert_eq!(
+            merge,
+            serde_json::json!({
+                "data": { "changed": "\"value\"", "removed": null },
+                "last_seen": session.last_seen,
+            })
+        );
+    }
 }
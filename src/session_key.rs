@@ -0,0 +1,27 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A Typed Key used to access a value within the Session's HashMap.
+///
+/// Binds the key's name to the type of value stored under it so a typo or
+/// a type change is caught at compile time rather than silently returning None.
+///
+/// # Examples
+/// ```rust
+/// use axum_session::SessionKey;
+///
+/// struct UserId;
+///
+/// impl SessionKey for UserId {
+///     const NAME: &'static str = "user-id";
+///     type Value = i64;
+/// }
+///
+/// assert_eq!(UserId::NAME, "user-id");
+/// ```
+///
+pub trait SessionKey {
+    /// The name the value is stored under within the Session's HashMap.
+    const NAME: &'static str;
+    /// The type of value stored under this key.
+    type Value: Serialize + DeserializeOwned;
+}
@@ -0,0 +1,110 @@
+use std::time::Instant;
+
+/// Requests handled by the `SessionLayer`.
+pub(crate) const REQUESTS: &str = "axum_session_requests_total";
+/// Time spent within `SessionService::call`, including the inner service.
+pub(crate) const REQUEST_DURATION: &str = "axum_session_request_duration_seconds";
+/// Sessions that were already loaded within memory.
+pub(crate) const MEMORY_HITS: &str = "axum_session_memory_hits_total";
+/// Sessions that had to be loaded from the database or created.
+pub(crate) const MEMORY_MISSES: &str = "axum_session_memory_misses_total";
+/// Sessions loaded from the database.
+pub(crate) const DATABASE_LOADS: &str = "axum_session_database_loads_total";
+/// Time spent loading a Session from the database.
+pub(crate) const LOAD_DURATION: &str = "axum_session_load_duration_seconds";
+/// Sessions stored to the database.
+pub(crate) const DATABASE_STORES: &str = "axum_session_database_stores_total";
+/// Time spent storing a Session to the database.
+pub(crate) const STORE_DURATION: &str = "axum_session_store_duration_seconds";
+/// Session IDs generated.
+pub(crate) const IDS_GENERATED: &str = "axum_session_ids_generated_total";
+/// Generated Session IDs thrown away because the bloom filter said they might exist.
+pub(crate) const BLOOM_REJECTIONS: &str = "axum_session_bloom_filter_rejections_total";
+/// Generated Session IDs thrown away because they existed within memory or the database.
+pub(crate) const ID_COLLISIONS: &str = "axum_session_id_collisions_total";
+/// Expired Sessions unloaded by the memory sweep.
+pub(crate) const MEMORY_PURGED: &str = "axum_session_memory_purged_total";
+/// Sessions evicted from memory because the memory capacity was reached.
+pub(crate) const MEMORY_EVICTED: &str = "axum_session_memory_evicted_total";
+/// Expired Sessions deleted by the database sweep.
+pub(crate) const DATABASE_PURGED: &str = "axum_session_database_purged_total";
+
+/// Adds value to the counter labelled with the table name.
+#[inline]
+pub(crate) fn increment(name: &'static str, table: &str, value: u64) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(name, "table" => table.to_owned()).increment(value);
+
+    #[cfg(not(feature = "metrics"))]
+    let _ = (name, table, value);
+}
+
+/// Records the seconds since start into the histogram labelled with the table name.
+#[inline]
+pub(crate) fn record_duration(name: &'static str, table: &str, start: Instant) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!(name, "table" => table.to_owned()).record(start.elapsed().as_secs_f64());
+
+    #[cfg(not(feature = "metrics"))]
+    let _ = (name, table, start);
+}
+
+/// Registers the descriptions and units of every metric the `SessionLayer` records
+/// with the installed `metrics` recorder. Call this once after installing the recorder.
+///
+/// Every metric is labelled with the `table` name of the `SessionStore`.
+///
+/// # Examples
+/// ```rust
+/// axum_session::describe_metrics();
+/// ```
+///
+#[cfg(feature = "metrics")]
+pub fn describe_metrics() {
+    use metrics::{describe_counter, describe_histogram, Unit};
+
+    describe_counter!(REQUESTS, "Requests handled by the SessionLayer.");
+    describe_histogram!(
+        REQUEST_DURATION,
+        Unit::Seconds,
+        "Time spent handling a request within the SessionLayer."
+    );
+    describe_counter!(
+        MEMORY_HITS,
+        "Sessions that were already loaded within memory."
+    );
+    describe_counter!(
+        MEMORY_MISSES,
+        "Sessions that had to be loaded from the database or created."
+    );
+    describe_counter!(DATABASE_LOADS, "Sessions loaded from the database.");
+    describe_histogram!(
+        LOAD_DURATION,
+        Unit::Seconds,
+        "Time spent loading a Session from the database."
+    );
+    describe_counter!(DATABASE_STORES, "Sessions stored to the database.");
+    describe_histogram!(
+        STORE_DURATION,
+        Unit::Seconds,
+        "Time spent storing a Session to the database."
+    );
+    describe_counter!(IDS_GENERATED, "Session IDs generated.");
+    describe_counter!(
+        BLOOM_REJECTIONS,
+        "Generated Session IDs rejected by the bloom filter."
+    );
+    describe_counter!(
+        ID_COLLISIONS,
+        "Generated Session IDs that already existed within memory or the database."
+    );
+    describe_counter!(MEMORY_PURGED, "Expired Sessions unloaded from memory.");
+    describe_counter!(
+        MEMORY_EVICTED,
+        "Sessions evicted from memory because the memory capacity was reached."
+    );
+    describe_counter!(
+        DATABASE_PURGED,
+        "Expired Sessions deleted from the database."
+    );
+}
@@ -1,8 +1,11 @@
 use crate::{
-    sec::encrypt, DatabasePool, Session, SessionConfig, SessionData, SessionError, SessionTimers,
+    codec, compression, headers::MAX_COOKIE_SESSION_SIZE, logging, sec::encrypt, session_metrics,
+    ConflictPolicy, DatabaseFailurePolicy, DatabasePool, FlashLevel, FlashMessage, MemoryCapacity,
+    OwnerLimitPolicy, Session, SessionCompression, SessionConfig, SessionData, SessionError,
+    SessionListener, SessionMetadata, SessionTimers,
 };
 use axum::extract::FromRequestParts;
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Duration, Utc};
 use dashmap::DashMap;
 #[cfg(feature = "key-store")]
 use fastbloom_rs::Deletable;
@@ -10,8 +13,19 @@ use fastbloom_rs::Deletable;
 use fastbloom_rs::{CountingBloomFilter, FilterBuilder, Membership};
 use http::{request::Parts, StatusCode};
 use serde::Serialize;
-use std::{fmt::Debug, sync::Arc};
-use tokio::sync::RwLock;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tokio::{
+    sync::{oneshot, RwLock},
+    task::JoinHandle,
+};
 
 /// Contains the main Services storage for all session's and database access for persistent Sessions.
 ///
@@ -39,6 +53,58 @@ where
     #[cfg(feature = "key-store")]
     /// Filter used to keep track of what session IDs exist.
     pub(crate) filter: Arc<RwLock<CountingBloomFilter>>,
+    /// Listeners told about the Session lifecycle events.
+    pub(crate) listeners: Arc<Vec<Arc<dyn SessionListener>>>,
+    /// Tracks if the database is unavailable and the writes waiting for it.
+    pub(crate) degraded: Arc<DegradedState>,
+    /// Estimated bytes of every session within memory, used by `MemoryCapacity::Bytes`.
+    pub(crate) memory_used: Arc<AtomicUsize>,
+}
+
+/// Used by `DatabaseFailurePolicy::ServeFromMemory` while the database is unavailable.
+#[derive(Debug, Default)]
+pub(crate) struct DegradedState {
+    pub(crate) active: AtomicBool,
+    /// Numbers the queued writes so they are replayed in the order they were made.
+    pub(crate) next_write: AtomicU64,
+    /// The latest save or removal of each Session and its number, replayed once the database is back.
+    pub(crate) pending: DashMap<String, (u64, PendingWrite)>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum PendingWrite {
+    Store(Box<SessionData>),
+    Remove,
+}
+
+/// Handle to the task started by `SessionStore::spawn_maintenance`.
+/// Dropping the handle also stops the task.
+#[derive(Debug)]
+pub struct MaintenanceHandle {
+    shutdown: Option<oneshot::Sender<()>>,
+    task: JoinHandle<()>,
+}
+
+impl MaintenanceHandle {
+    /// Stops the maintenance task, waiting for a sweep that is already running to finish.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// maintenance.cancel().await;
+    /// ```
+    ///
+    pub async fn cancel(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+
+        let _ = (&mut self.task).await;
+    }
+
+    /// Returns true if the maintenance task has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
 }
 
 impl<T, S> FromRequestParts<S> for SessionStore<T>
@@ -75,7 +141,25 @@ where
     ///
     #[inline]
     pub async fn new(client: Option<T>, config: SessionConfig) -> Result<Self, SessionError> {
+        codec::validate(config.database.codec.as_ref())?;
+
         if let Some(client) = &client {
+            if client.stores_in_cookie() {
+                if !config.cookie_and_header.transport.uses_cookies() {
+                    return Err(SessionError::GenericNotSupportedError(
+                        "Sessions can not be stored within cookies when only headers are used."
+                            .to_owned(),
+                    ));
+                }
+
+                if config.cookie_and_header.key.is_none() {
+                    return Err(SessionError::GenericNotSupportedError(
+                        "A key must be set with `SessionConfig::with_key` to store Sessions within cookies."
+                            .to_owned(),
+                    ));
+                }
+            }
+
             client.initiate(&config.database.table_name).await?
         }
 
@@ -93,12 +177,70 @@ where
                 last_expiry_sweep: Utc::now() + Duration::try_hours(1).unwrap_or_default(),
                 // the first expiry sweep is scheduled one lifetime from start-up
                 last_database_expiry_sweep: Utc::now() + Duration::try_hours(6).unwrap_or_default(),
+                next_database_retry: Utc::now(),
+                next_eviction_scan: Utc::now(),
             })),
             #[cfg(feature = "key-store")]
             filter: Arc::new(RwLock::new(filter)),
+            listeners: Default::default(),
+            degraded: Default::default(),
+            memory_used: Default::default(),
         })
     }
 
+    /// Adds a listener that is told when Sessions are created, loaded, renewed, destroyed or expired.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore};
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config)
+    ///     .await
+    ///     .unwrap()
+    ///     .with_listener(AuditLog);
+    /// ```
+    ///
+    #[must_use]
+    pub fn with_listener(mut self, listener: impl SessionListener) -> Self {
+        Arc::make_mut(&mut self.listeners).push(Arc::new(listener));
+        self
+    }
+
+    pub(crate) async fn notify_create(&self, id: &str) {
+        for listener in self.listeners.iter() {
+            listener.on_create(id).await;
+        }
+    }
+
+    pub(crate) async fn notify_load(&self, id: &str) {
+        for listener in self.listeners.iter() {
+            listener.on_load(id).await;
+        }
+    }
+
+    pub(crate) async fn notify_renew(&self, old_id: &str, new_id: &str) {
+        for listener in self.listeners.iter() {
+            listener.on_renew(old_id, new_id).await;
+        }
+    }
+
+    pub(crate) async fn notify_destroy(&self, id: &str) {
+        for listener in self.listeners.iter() {
+            listener.on_destroy(id).await;
+        }
+    }
+
+    pub(crate) async fn notify_expire(&self, ids: &[String]) {
+        if ids.is_empty() {
+            return;
+        }
+
+        for listener in self.listeners.iter() {
+            listener.on_expire(ids).await;
+        }
+    }
+
     /// Used to create and Fill the Filter.
     #[cfg(feature = "key-store")]
     pub(crate) async fn create_filter(
@@ -141,6 +283,196 @@ where
         self.client.is_some()
     }
 
+    /// Checks if the whole Session is stored within the cookies.
+    #[inline]
+    pub(crate) fn is_cookie_store(&self) -> bool {
+        self.client
+            .as_ref()
+            .is_some_and(|client| client.stores_in_cookie())
+    }
+
+    /// Checks if Sessions evicted from memory can be loaded again from the database.
+    /// Sessions without a database or stored within the cookies are never evicted.
+    #[inline]
+    pub(crate) fn can_evict(&self) -> bool {
+        self.client
+            .as_ref()
+            .is_some_and(|client| !client.stores_in_cookie() && !client.stores_nothing())
+    }
+
+    /// Checks if the database is unavailable and Sessions are only served from memory.
+    /// This can only happen when `DatabaseFailurePolicy::ServeFromMemory` is set.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore};
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config).await.unwrap();
+    /// let degraded = session_store.is_degraded();
+    /// ```
+    ///
+    #[inline]
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.active.load(Ordering::Acquire)
+    }
+
+    /// Returns how many Session saves and removals are waiting for the database to be available again.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore};
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config).await.unwrap();
+    /// let pending = session_store.pending_writes();
+    /// ```
+    ///
+    #[inline]
+    pub fn pending_writes(&self) -> usize {
+        self.degraded.pending.len()
+    }
+
+    /// Enters degraded mode if the error means the database is unavailable and the
+    /// `DatabaseFailurePolicy` allows serving from memory.
+    ///
+    /// Returns false if the error should be returned instead.
+    pub(crate) async fn degrade(&self, err: &SessionError) -> bool {
+        let DatabaseFailurePolicy::ServeFromMemory(retry) = self.config.database.failure_policy
+        else {
+            return false;
+        };
+
+        if !matches!(err, SessionError::DatabaseError(_)) {
+            return false;
+        }
+
+        if !self.degraded.active.swap(true, Ordering::AcqRel) {
+            tracing::error!(err = %err, "Session database is unavailable. Serving Sessions from memory.");
+            self.timers.write().await.next_database_retry = Utc::now() + retry;
+        }
+
+        true
+    }
+
+    /// Queues the Session to be stored once the database is available again.
+    /// It is always fully stored since the partial changes may have been merged with later ones.
+    pub(crate) fn queue_store(&self, mut session: SessionData) {
+        session.full_write = true;
+        self.queue_write(session.id.clone(), PendingWrite::Store(Box::new(session)));
+    }
+
+    fn queue_write(&self, id: String, write: PendingWrite) {
+        self.degraded
+            .pending
+            .insert(id, (self.write_order(), write));
+    }
+
+    #[inline]
+    fn write_order(&self) -> u64 {
+        self.degraded.next_write.fetch_add(1, Ordering::AcqRel)
+    }
+
+    /// Checks if the database is available again once the retry Duration passed and
+    /// replays the queued writes. Degraded mode ends once every write was replayed.
+    pub(crate) async fn retry_database(&self) {
+        let DatabaseFailurePolicy::ServeFromMemory(retry) = self.config.database.failure_policy
+        else {
+            return;
+        };
+
+        if !self.is_degraded() {
+            return;
+        }
+
+        {
+            let mut timers = self.timers.write().await;
+
+            if timers.next_database_retry > Utc::now() {
+                return;
+            }
+
+            timers.next_database_retry = Utc::now() + retry;
+        }
+
+        let Some(client) = &self.client else {
+            return;
+        };
+
+        if let Err(err) = client.count(&self.config.database.table_name).await {
+            tracing::warn!(err = %err, "Session database is still unavailable.");
+            return;
+        }
+
+        if !self.replay_pending().await {
+            return;
+        }
+
+        self.degraded.active.store(false, Ordering::Release);
+
+        // Anything queued while we were replaying is stored now that new writes go to the database.
+        if self.replay_pending().await {
+            tracing::info!("Session database is available again.");
+        }
+    }
+
+    /// Stores or removes every queued Session in the order they were queued.
+    /// Returns false if the database became unavailable again.
+    async fn replay_pending(&self) -> bool {
+        let mut queued: Vec<(u64, String)> = self
+            .degraded
+            .pending
+            .iter()
+            .map(|r| (r.value().0, r.key().clone()))
+            .collect();
+
+        queued.sort_unstable();
+
+        for (_, id) in queued {
+            // Writes stay queued until they are stored so newer ones replace them instead of racing them.
+            while let Some((order, write)) =
+                self.degraded.pending.get(&id).map(|r| r.value().clone())
+            {
+                if let Err(err) = self.replay_write(&id, write).await {
+                    if matches!(err, SessionError::DatabaseError(_)) {
+                        tracing::warn!(err = %err, "Session database became unavailable while replaying writes.");
+                        self.degraded.active.store(true, Ordering::Release);
+                        return false;
+                    }
+
+                    tracing::error!(err = %err, session = %logging::LogId(&id), "Dropped a queued Session write.");
+                }
+
+                // Replay it again if a newer write replaced it while it was stored.
+                if self
+                    .degraded
+                    .pending
+                    .remove_if(&id, |_, (queued, _)| *queued == order)
+                    .is_some()
+                {
+                    break;
+                }
+            }
+        }
+
+        true
+    }
+
+    async fn replay_write(&self, id: &str, write: PendingWrite) -> Result<(), SessionError> {
+        match write {
+            PendingWrite::Store(session) => {
+                let owner = session.owner_changed.then(|| session.owner.clone());
+                self.save_session_with_policy(*session, None).await?;
+
+                match owner {
+                    Some(owner) => self.store_owner(id, owner).await,
+                    None => Ok(()),
+                }
+            }
+            PendingWrite::Remove => self.remove_from_database(id).await,
+        }
+    }
+
     /// Cleans Expired sessions from the Database based on Utc::now().
     ///
     /// If client is None it will return Ok(()).
@@ -162,9 +494,17 @@ where
     #[inline]
     pub async fn cleanup(&self) -> Result<Vec<String>, SessionError> {
         if let Some(client) = &self.client {
-            Ok(client
+            let expired = client
                 .delete_by_expiry(&self.config.database.table_name)
-                .await?)
+                .await?;
+
+            session_metrics::increment(
+                session_metrics::DATABASE_PURGED,
+                &self.config.database.table_name,
+                expired.len() as u64,
+            );
+            self.notify_expire(&expired).await;
+            Ok(expired)
         } else {
             Ok(Vec::new())
         }
@@ -222,80 +562,1014 @@ where
     pub(crate) async fn load_session(
         &self,
         cookie_value: String,
+    ) -> Result<Option<SessionData>, SessionError> {
+        // Writes that have not reached the database yet are newer than what it has.
+        if let Some(write) = self.degraded.pending.get(&cookie_value) {
+            return Ok(match &write.value().1 {
+                PendingWrite::Store(session) => Some(*session.clone()),
+                PendingWrite::Remove => None,
+            });
+        }
+
+        if self.is_degraded() {
+            return Ok(None);
+        }
+
+        match self.load_from_database(cookie_value).await {
+            Err(err) if self.degrade(&err).await => Ok(None),
+            result => result,
+        }
+    }
+
+    async fn load_from_database(
+        &self,
+        cookie_value: String,
     ) -> Result<Option<SessionData>, SessionError> {
         if let Some(client) = &self.client {
+            let start = Instant::now();
             let result: Option<String> = client
                 .load(&cookie_value, &self.config.database.table_name)
                 .await?;
 
+            let table = &self.config.database.table_name;
+            session_metrics::record_duration(session_metrics::LOAD_DURATION, table, start);
+
+            if result.is_some() {
+                session_metrics::increment(session_metrics::DATABASE_LOADS, table, 1);
+            }
+
+            let mut reencrypt = false;
+
             if let Some(mut session) = result
                 .map(|session| {
                     if let Some(key) = self.config.database.database_key.as_ref() {
-                        serde_json::from_str::<SessionData>(
-                            &match encrypt::decrypt(&cookie_value, &session, key) {
-                                Ok(v) => v,
+                        self.deserialize_session(
+                            match encrypt::decrypt_with_retired(
+                                &cookie_value,
+                                &session,
+                                key,
+                                &self.config.database.retired_database_keys,
+                            ) {
+                                Ok((v, needs_reencrypt)) => {
+                                    reencrypt = needs_reencrypt;
+                                    v
+                                }
                                 Err(err) => {
                                     tracing::error!(err = %err, "Failed to decrypt Session data from database.");
                                     String::new()
                                 }
-                            }
+                            },
                         )
                     } else {
-                        serde_json::from_str::<SessionData>(&session)
+                        self.deserialize_session(session)
                     }
                 })
                 .transpose()?
             {
+                // Sessions encrypted by a retired key get fully stored with the active key
+                // at the end of this request instead of only being touched.
+                if reencrypt {
+                    session.update = true;
+                    session.full_write = true;
+                }
+
                 session.id = cookie_value;
                 return Ok(Some(session));
             }
         }
 
-        Ok(None)
+        Ok(None)
+    }
+
+    /// private internal function that stores a session's data to the database.
+    ///
+    /// If client is None it will return Ok(()).
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    /// - ['SessionError::SerdeJson'] is returned if it failed to serialize the sessions data.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore, SessionData};
+    /// use uuid::Uuid;
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config.clone()).await.unwrap();
+    /// let token = Uuid::new_v4();
+    /// let session_data = SessionData::new(token, true, &config);
+    ///
+    /// async {
+    ///     let _ = session_store.store_session(&session_data).await.unwrap();
+    /// };
+    /// ```
+    ///
+    pub(crate) async fn store_session(&self, session: &SessionData) -> Result<(), SessionError> {
+        if let Some(client) = &self.client {
+            // The database increases its stored version the same as compare and store does.
+            let mut next = session.clone();
+            next.version += 1;
+            let value = self.serialize_session(next)?;
+
+            let start = Instant::now();
+            client
+                .store(
+                    &session.id,
+                    &value,
+                    session.expires.timestamp(),
+                    &self.config.database.table_name,
+                )
+                .await?;
+
+            self.record_store(start);
+
+            if let Some(mut instance) = self.inner.get_mut(&session.id) {
+                if instance.version == session.version {
+                    instance.version += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// private internal function that stores only the data keys of a session that changed.
+    /// Encrypted sessions and sessions that changed more than their data are never partially stored.
+    ///
+    /// Returns Ok(false) if the session still needs to be fully stored.
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    ///
+    pub(crate) async fn store_partial_session(
+        &self,
+        session: &SessionData,
+    ) -> Result<bool, SessionError> {
+        if session.full_write
+            || self.config.database.database_key.is_some()
+            || self.config.database.codec.header() != codec::JSON_HEADER
+            || self.config.database.compression != SessionCompression::None
+        {
+            return Ok(false);
+        }
+
+        if let Some(client) = &self.client {
+            let start = Instant::now();
+            let stored = client
+                .store_partial(
+                    &session.id,
+                    &session.patch(),
+                    session.expires.timestamp(),
+                    &self.config.database.table_name,
+                )
+                .await?;
+
+            if stored {
+                self.record_store(start);
+            }
+
+            return Ok(stored);
+        }
+
+        Ok(false)
+    }
+
+    /// private internal function that stores a session's data to the database
+    /// only if the version stored within the database matches the sessions version.
+    ///
+    /// If client is None it will return Ok(true).
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    /// - ['SessionError::SerdeJson'] is returned if it failed to serialize the sessions data.
+    ///
+    pub(crate) async fn compare_and_store_session(
+        &self,
+        session: &SessionData,
+    ) -> Result<bool, SessionError> {
+        if let Some(client) = &self.client {
+            let mut next = session.clone();
+            next.version += 1;
+            let value = self.serialize_session(next)?;
+
+            let start = Instant::now();
+            let stored = client
+                .compare_and_store(
+                    &session.id,
+                    &value,
+                    session.expires.timestamp(),
+                    session.version,
+                    &self.config.database.table_name,
+                )
+                .await?;
+
+            if stored {
+                self.record_store(start);
+            }
+
+            return Ok(stored);
+        }
+
+        Ok(true)
+    }
+
+    /// Records a Session that was stored to the database and how long it took.
+    #[inline]
+    fn record_store(&self, start: Instant) {
+        let table = &self.config.database.table_name;
+        session_metrics::record_duration(session_metrics::STORE_DURATION, table, start);
+        session_metrics::increment(session_metrics::DATABASE_STORES, table, 1);
+    }
+
+    /// private internal function that saves a session to the database using the configured `ConflictPolicy`.
+    /// original is the sessions data from before the request ran and is used to merge on conflicts.
+    ///
+    /// # Errors
+    /// - ['SessionError::VersionConflict'] is returned if a newer version was stored and could not be merged.
+    ///
+    pub(crate) async fn save_session(
+        &self,
+        session: SessionData,
+        original: Option<&HashMap<String, String>>,
+    ) -> Result<(), SessionError> {
+        if self.is_degraded() {
+            self.queue_store(session);
+            return Ok(());
+        }
+
+        // A queued write still being replayed is replaced so it can not overwrite this newer one.
+        if let Some(mut queued) = self.degraded.pending.get_mut(&session.id) {
+            let mut session = session;
+            session.full_write = true;
+            *queued = (self.write_order(), PendingWrite::Store(Box::new(session)));
+            return Ok(());
+        }
+
+        // Keep a copy to queue in case the database is unavailable.
+        let queued = matches!(
+            self.config.database.failure_policy,
+            DatabaseFailurePolicy::ServeFromMemory(_)
+        )
+        .then(|| session.clone());
+
+        let owner = session.owner_changed.then(|| session.owner.clone());
+        let id = session.id.clone();
+
+        let result = match self.save_session_with_policy(session, original).await {
+            Ok(()) => match owner {
+                Some(owner) => self.store_owner(&id, owner).await,
+                None => Ok(()),
+            },
+            Err(err) => Err(err),
+        };
+
+        match (result, queued) {
+            (Err(err), Some(session)) if self.degrade(&err).await => {
+                self.queue_store(session);
+                Ok(())
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// private internal function that stores the sessions owner within the databases owner index.
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    ///
+    pub(crate) async fn store_owner(
+        &self,
+        id: &str,
+        owner: Option<String>,
+    ) -> Result<(), SessionError> {
+        if let Some(client) = &self.client {
+            client
+                .set_owner(id, owner.as_deref(), &self.config.database.table_name)
+                .await?;
+        }
+
+        if let Some(mut instance) = self.inner.get_mut(id) {
+            // Only mark it as stored if it was not changed again while we were storing it.
+            if instance.owner == owner {
+                instance.owner_changed = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn save_session_with_policy(
+        &self,
+        session: SessionData,
+        original: Option<&HashMap<String, String>>,
+    ) -> Result<(), SessionError> {
+        if session.recreated {
+            if let Some(client) = &self.client {
+                if client
+                    .exists(&session.id, &self.config.database.table_name)
+                    .await?
+                {
+                    // The database still has the real Session so this copy is unloaded
+                    // and the next request loads the stored one instead.
+                    if let Some((_, unloaded)) = self
+                        .inner
+                        .remove_if(&session.id, |_, instance| instance.requests <= 1)
+                    {
+                        self.memory_used
+                            .fetch_sub(unloaded.counted_size, Ordering::Relaxed);
+                    }
+
+                    return Ok(());
+                }
+            }
+
+            let id = session.id.clone();
+            self.save_session_with_conflict_policy(session, original)
+                .await?;
+
+            if let Some(mut instance) = self.inner.get_mut(&id) {
+                instance.recreated = false;
+            }
+
+            return Ok(());
+        }
+
+        self.save_session_with_conflict_policy(session, original)
+            .await
+    }
+
+    async fn save_session_with_conflict_policy(
+        &self,
+        mut session: SessionData,
+        original: Option<&HashMap<String, String>>,
+    ) -> Result<(), SessionError> {
+        let retries = match self.config.database.conflict_policy {
+            ConflictPolicy::LastWriteWins => {
+                let result = match self.store_partial_session(&session).await {
+                    Ok(true) => {
+                        // Partial writes increase the stored version like compare and store does.
+                        if let Some(mut instance) = self.inner.get_mut(&session.id) {
+                            if instance.version == session.version {
+                                instance.version += 1;
+                            }
+                        }
+
+                        Ok(())
+                    }
+                    Ok(false) => self.store_session(&session).await,
+                    Err(err) => Err(err),
+                };
+
+                // The changes were forgotten when we took them so store everything next time.
+                if result.is_err() {
+                    if let Some(mut instance) = self.inner.get_mut(&session.id) {
+                        instance.full_write = true;
+                    }
+                }
+
+                return result;
+            }
+            ConflictPolicy::Error => 0,
+            ConflictPolicy::RetryWithMerge(retries) => retries,
+        };
+
+        let mut attempts = 0;
+
+        loop {
+            if self.compare_and_store_session(&session).await? {
+                if let Some(mut instance) = self.inner.get_mut(&session.id) {
+                    instance.version = session.version + 1;
+
+                    if attempts > 0 {
+                        instance.data = session.data;
+                    }
+                }
+
+                return Ok(());
+            }
+
+            let Some(original) = original else {
+                return Err(SessionError::VersionConflict);
+            };
+
+            if attempts >= retries {
+                return Err(SessionError::VersionConflict);
+            }
+
+            attempts += 1;
+
+            match self.load_session(session.id.clone()).await? {
+                Some(stored) => session.rebase(stored, original),
+                // It was removed from the database so lets store it as new again.
+                None => session.version = 0,
+            }
+        }
+    }
+
+    /// Serializes the session with the configured codec, compresses it
+    /// and then encrypts it if a database key is set.
+    pub(crate) fn serialize_session(&self, session: SessionData) -> Result<String, SessionError> {
+        let id = session.id.clone();
+        let value = compression::compress(
+            self.config.database.compression,
+            self.config.database.compression_threshold,
+            codec::encode(self.config.database.codec.as_ref(), session)?,
+        )?;
+
+        Ok(
+            if let Some(key) = self.config.database.database_key.as_ref() {
+                encrypt::encrypt(&id, &value, key).map_err(|e| {
+                    SessionError::GenericNotSupportedError(format!(
+                        "Error: {e} Occurred when encrypting a Session.",
+                    ))
+                })?
+            } else {
+                value
+            },
+        )
+    }
+
+    /// Decompresses the decrypted session if needed and deserializes it with the codec that stored it.
+    pub(crate) fn deserialize_session(&self, value: String) -> Result<SessionData, SessionError> {
+        codec::decode(
+            self.config.database.codec.as_ref(),
+            &compression::decompress(value)?,
+        )
+    }
+
+    /// Serializes the session and encrypts it with the cookie key so it can be stored within the cookies.
+    /// The expiration and ID are placed within the encrypted value so they can not be changed by the client.
+    /// It is never compressed, as the size of compressed secrets next to data the client controls
+    /// would leak them through the cookies length (CRIME/BREACH).
+    pub(crate) fn serialize_cookie_session(
+        &self,
+        session: SessionData,
+    ) -> Result<String, SessionError> {
+        let Some(key) = self.config.cookie_and_header.key.as_ref() else {
+            return Err(SessionError::GenericNotSupportedError(
+                "A key must be set to store Sessions within cookies.".to_owned(),
+            ));
+        };
+
+        let expires = session.next_expiry(&self.config);
+        let id = session.id.clone();
+
+        let value = codec::encode(self.config.database.codec.as_ref(), session)?;
+
+        let value = encrypt::encrypt(
+            &self.config.cookie_and_header.session_name,
+            &format!("{}\n{id}\n{value}", expires.timestamp()),
+            key,
+        )
+        .map_err(|e| {
+            SessionError::GenericNotSupportedError(format!(
+                "Error: {e} Occurred when encrypting a Session cookie.",
+            ))
+        })?;
+
+        if value.len() > MAX_COOKIE_SESSION_SIZE {
+            return Err(SessionError::GenericNotSupportedError(format!(
+                "Session is {} bytes which is too large to be stored within the cookies.",
+                value.len()
+            )));
+        }
+
+        Ok(value)
+    }
+
+    /// Decrypts and deserializes a session stored within the cookies.
+    /// Returns None if it was tampered with or has expired.
+    pub(crate) fn deserialize_cookie_session(&self, value: &str) -> Option<SessionData> {
+        let key = self.config.cookie_and_header.key.as_ref()?;
+
+        let value = match encrypt::decrypt_with_retired(
+            &self.config.cookie_and_header.session_name,
+            value,
+            key,
+            &self.config.cookie_and_header.retired_keys,
+        ) {
+            // Cookie sessions are encrypted with the active key on every response.
+            Ok((v, _)) => v,
+            Err(err) => {
+                tracing::warn!(err = %err, "Failed to decrypt Session cookie.");
+                return None;
+            }
+        };
+
+        let mut parts = value.splitn(3, '\n');
+        let (Some(expires), Some(id), Some(data)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+
+        let expires = DateTime::from_timestamp(expires.parse().ok()?, 0)?;
+
+        if expires < Utc::now() {
+            return None;
+        }
+
+        match self.deserialize_session(data.to_owned()) {
+            Ok(mut session) => {
+                session.id = id.to_owned();
+                session.expires = expires;
+                Some(session)
+            }
+            Err(err) => {
+                tracing::warn!(err = %err, "Failed to deserialize Session cookie.");
+                None
+            }
+        }
+    }
+
+    /// Encrypts every session in the database again using the active database key.
+    /// Sessions already encrypted by the active key are skipped.
+    /// Returns the number of sessions that were rewritten.
+    ///
+    /// Sessions are also encrypted again when they get stored after loading, so this
+    /// is only needed to remove a retired key before every session has been used again.
+    ///
+    /// Sessions keep the expiry stored within the database. Sessions whose expiry can not be
+    /// read using `DatabasePool::load_expiry` are skipped rather than given a new lifetime.
+    ///
+    /// If client is None or no database key is set it will return Ok(0).
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore, Key};
+    ///
+    /// let config = SessionConfig::default()
+    ///     .with_database_key(Key::generate())
+    ///     .with_retired_database_key(Key::generate());
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config.clone()).await.unwrap();
+    ///
+    /// async {
+    ///     let rewritten = session_store.reencrypt_all().await.unwrap();
+    /// };
+    /// ```
+    ///
+    pub async fn reencrypt_all(&self) -> Result<usize, SessionError> {
+        let (Some(client), Some(key)) = (&self.client, &self.config.database.database_key) else {
+            return Ok(0);
+        };
+
+        let table_name = &self.config.database.table_name;
+        let key_id = encrypt::key_id(key);
+        let mut count = 0;
+
+        for id in client.get_ids(table_name).await? {
+            let Some(value) = client.load(&id, table_name).await? else {
+                continue;
+            };
+
+            if encrypt::split_key_id(&value).0 == Some(key_id.as_str()) {
+                continue;
+            }
+
+            let mut session = match encrypt::decrypt_with_retired(
+                &id,
+                &value,
+                key,
+                &self.config.database.retired_database_keys,
+            ) {
+                // Values encrypted by the active key were already skipped above.
+                Ok((v, _)) => self.deserialize_session(v)?,
+                Err(err) => {
+                    tracing::error!(err = %err, "Failed to decrypt Session data from database.");
+                    continue;
+                }
+            };
+
+            // Keep the stored expiry so rotating the key never extends a Session's lifetime.
+            let Some(expires) = client
+                .load_expiry(&id, table_name)
+                .await?
+                .and_then(|expires| DateTime::from_timestamp(expires, 0))
+            else {
+                tracing::warn!(
+                    session = %logging::LogId(&id),
+                    "Session was not re-encrypted as its expiry could not be read from the database."
+                );
+                continue;
+            };
+
+            session.id = id;
+            session.expires = expires;
+
+            if self.config.database.conflict_policy.is_versioned() {
+                // If it changed since we loaded it, it was already encrypted by the active key.
+                if !self.compare_and_store_session(&session).await? {
+                    continue;
+                }
+
+                // Keep loaded copies in sync so their next save does not conflict.
+                if let Some(mut instance) = self.inner.get_mut(&session.id) {
+                    if instance.version == session.version {
+                        instance.version += 1;
+                    }
+                }
+            } else {
+                self.store_session(&session).await?;
+            }
+
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Returns the id's of every session owned by the owner set with `Session::set_owner`.
+    /// This includes sessions only in memory and sessions within the database.
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore};
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config.clone()).await.unwrap();
+    ///
+    /// async {
+    ///     let ids = session_store.list_by_owner("user-1").await.unwrap();
+    /// };
+    /// ```
+    ///
+    pub async fn list_by_owner(&self, owner: &str) -> Result<Vec<String>, SessionError> {
+        self.collect_by_owner(owner, |session| session.id.clone())
+            .await
+    }
+
+    /// Returns the metadata of a session from memory or the database.
+    /// Returns None if the session does not exist.
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore};
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config.clone()).await.unwrap();
+    ///
+    /// async {
+    ///     let metadata = session_store.get_metadata(&session.get_session_id()).await.unwrap();
+    /// };
+    /// ```
+    ///
+    pub async fn get_metadata(&self, id: &str) -> Result<Option<SessionMetadata>, SessionError> {
+        if let Some(session) = self.inner.get(id) {
+            return Ok(Some(session.metadata()));
+        }
+
+        Ok(self
+            .load_session(id.to_owned())
+            .await?
+            .map(|session| session.metadata()))
+    }
+
+    /// Returns the metadata of every session owned by the owner set with `Session::set_owner`
+    /// or `Session::login`. Used to show a user the devices they are signed in on.
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore};
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config.clone()).await.unwrap();
+    ///
+    /// async {
+    ///     let devices = session_store.list_metadata_by_owner("user-1").await.unwrap();
+    /// };
+    /// ```
+    ///
+    pub async fn list_metadata_by_owner(
+        &self,
+        owner: &str,
+    ) -> Result<Vec<SessionMetadata>, SessionError> {
+        self.collect_by_owner(owner, SessionData::metadata).await
+    }
+
+    /// private internal function that maps every session owned by the owner
+    /// within memory and the database.
+    async fn collect_by_owner<R>(
+        &self,
+        owner: &str,
+        map: impl Fn(&SessionData) -> R,
+    ) -> Result<Vec<R>, SessionError> {
+        let mut ids: Vec<String> = Vec::new();
+        let mut found: Vec<R> = Vec::new();
+
+        for session in self
+            .inner
+            .iter()
+            .filter(|r| r.owner.as_deref() == Some(owner))
+        {
+            ids.push(session.key().clone());
+            found.push(map(&session));
+        }
+
+        // While degraded only the sessions within memory and the queued writes are known.
+        let mut stored = match &self.client {
+            Some(client) if !self.is_degraded() => {
+                match client
+                    .get_ids_by_owner(owner, &self.config.database.table_name)
+                    .await
+                    .map_err(SessionError::from)
+                {
+                    Err(err) if self.degrade(&err).await => Vec::new(),
+                    result => result?,
+                }
+            }
+            _ => Vec::new(),
+        };
+
+        stored.extend(
+            self.degraded
+                .pending
+                .iter()
+                .filter(|r| match &r.value().1 {
+                    PendingWrite::Store(session) => session.owner.as_deref() == Some(owner),
+                    PendingWrite::Remove => false,
+                })
+                .map(|r| r.key().clone()),
+        );
+
+        for id in stored {
+            if ids.contains(&id) || self.inner.contains_key(&id) {
+                continue;
+            }
+
+            // The index might be out of date so make sure it is still owned by them.
+            if let Some(session) = self.load_session(id.clone()).await? {
+                if session.owner.as_deref() == Some(owner) {
+                    found.push(map(&session));
+                    ids.push(id);
+                }
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Destroys every session owned by the owner within memory and the database.
+    /// Used to log a user out everywhere after a password reset or when their account was compromised.
+    /// except_current can be set to a session id that should be kept, like the one making the request.
+    ///
+    /// Returns the number of sessions that were destroyed.
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    ///
+    /// # Examples
+    /// ```rust ignore
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore};
+    ///
+    /// let config = SessionConfig::default();
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config.clone()).await.unwrap();
+    ///
+    /// async {
+    ///     let count = session_store.destroy_all_for_owner("user-1", Some(&session.get_session_id())).await.unwrap();
+    /// };
+    /// ```
+    ///
+    pub async fn destroy_all_for_owner(
+        &self,
+        owner: &str,
+        except_current: Option<&str>,
+    ) -> Result<usize, SessionError> {
+        let ids: Vec<String> = self
+            .list_by_owner(owner)
+            .await?
+            .into_iter()
+            .filter(|id| Some(id.as_str()) != except_current)
+            .collect();
+
+        self.destroy_sessions(&ids).await?;
+        Ok(ids.len())
+    }
+
+    /// private internal function that destroys sessions within memory, the database and the filter.
+    async fn destroy_sessions(&self, ids: &[String]) -> Result<(), SessionError> {
+        for id in ids {
+            self.memory_remove(id);
+            self.database_remove_session(id.clone()).await?;
+            self.notify_destroy(id).await;
+        }
+
+        #[cfg(feature = "key-store")]
+        if self.config.memory.use_bloom_filters {
+            let mut filter = self.filter.write().await;
+            ids.iter().for_each(|id| filter.remove(id.as_bytes()));
+        }
+
+        Ok(())
     }
 
-    /// private internal function that stores a session's data to the database.
+    /// private internal function that applies `SessionConfig::with_max_sessions_per_owner`
+    /// to a session that was just given the owner.
     ///
-    /// If client is None it will return Ok(()).
+    /// Returns Ok(false) if the owner was refused. The `SessionLayer` rolls the session back.
     ///
     /// # Errors
     /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
-    /// - ['SessionError::SerdeJson'] is returned if it failed to serialize the sessions data.
+    ///
+    pub(crate) async fn enforce_owner_limit(
+        &self,
+        id: &str,
+        owner: &str,
+    ) -> Result<bool, SessionError> {
+        let Some((max, policy)) = self.config.max_sessions_per_owner else {
+            return Ok(true);
+        };
+
+        let mut others: Vec<(DateTime<Utc>, String)> = self
+            .collect_by_owner(owner, |session| (session.created_at, session.id.clone()))
+            .await?
+            .into_iter()
+            .filter(|(_, other)| other != id)
+            .collect();
+
+        if others.len() < max {
+            return Ok(true);
+        }
+
+        match policy {
+            OwnerLimitPolicy::EvictOldest => {
+                // Leave room for the new session.
+                let over = others.len() + 1 - max;
+                others.sort();
+
+                let evict: Vec<String> = others.into_iter().take(over).map(|(_, id)| id).collect();
+
+                self.destroy_sessions(&evict).await?;
+                Ok(true)
+            }
+            OwnerLimitPolicy::RefuseNew => Ok(false),
+        }
+    }
+
+    /// Spawns a task onto the runtime that periodically unloads expired sessions from memory,
+    /// deletes expired sessions from the database and updates the bloom filter.
+    /// Use `SessionConfig::with_inline_maintenance(false)` so requests no longer run these sweeps.
+    ///
+    /// The task stops when the returned `MaintenanceHandle` is cancelled or dropped.
     ///
     /// # Examples
     /// ```rust ignore
-    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore, SessionData};
-    /// use uuid::Uuid;
-    ///
-    /// let config = SessionConfig::default();
-    /// let session_store = SessionStore::<SessionNullPool>::new(None, config.clone()).await.unwrap();
-    /// let token = Uuid::new_v4();
-    /// let session_data = SessionData::new(token, true, &config);
+    /// use axum_session::{SessionNullPool, SessionConfig, SessionStore};
     ///
-    /// async {
-    ///     let _ = session_store.store_session(&session_data).await.unwrap();
-    /// };
+    /// let config = SessionConfig::default().with_inline_maintenance(false);
+    /// let session_store = SessionStore::<SessionNullPool>::new(None, config).await.unwrap();
+    /// let maintenance = session_store.spawn_maintenance(&tokio::runtime::Handle::current());
     /// ```
     ///
-    pub(crate) async fn store_session(&self, session: &SessionData) -> Result<(), SessionError> {
-        if let Some(client) = &self.client {
-            client
-                .store(
-                    &session.id,
-                    &if let Some(key) = self.config.database.database_key.as_ref() {
-                        encrypt::encrypt(&session.id, &serde_json::to_string(session)?, key)
-                            .map_err(|e| {
-                                SessionError::GenericNotSupportedError(format!(
-                                    "Error: {e} Occurred when encrypting a Session.",
-                                ))
-                            })?
-                    } else {
-                        serde_json::to_string(session)?
-                    },
-                    session.expires.timestamp(),
-                    &self.config.database.table_name,
-                )
-                .await?;
+    pub fn spawn_maintenance(&self, handle: &tokio::runtime::Handle) -> MaintenanceHandle {
+        let store = self.clone();
+        let (shutdown, mut cancelled) = oneshot::channel::<()>();
+        let period = std::cmp::min(
+            self.config.memory.purge_update,
+            self.config.database.purge_database_update,
+        )
+        .to_std()
+        .unwrap_or_default()
+        .max(std::time::Duration::from_secs(1));
+
+        let task = handle.spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                tokio::select! {
+                    _ = &mut cancelled => break,
+                    _ = interval.tick() => {
+                        if let Err(err) = store.run_maintenance().await {
+                            tracing::error!(err = %err, "Session maintenance failed.");
+                        }
+                    }
+                }
+            }
+        });
+
+        MaintenanceHandle {
+            shutdown: Some(shutdown),
+            task,
+        }
+    }
+
+    /// Unloads expired sessions from memory and deletes expired sessions from the database
+    /// once their purge timers have passed. This also removes them from the bloom filter.
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    ///
+    pub(crate) async fn run_maintenance(&self) -> Result<(), SessionError> {
+        self.retry_database().await;
+
+        let (last_sweep, last_database_sweep) = {
+            let timers = self.timers.read().await;
+            (timers.last_expiry_sweep, timers.last_database_expiry_sweep)
+        };
+
+        // This branch runs less often, and we already have write access,
+        // let's check if any sessions expired. We don't want to hog memory
+        // forever by abandoned sessions (e.g. when a client lost their cookie)
+        // throttle by memory lifespan - e.g. sweep every hour
+        let current_time = Utc::now();
+
+        if last_sweep <= current_time && !self.config.memory.memory_lifespan.is_zero() {
+            logging::log(
+                self.config.log_level,
+                None,
+                "Session Memory Cleaning Started",
+            );
+            // Only unload these from filter if the Client is None as this means no database.
+            // Otherwise only unload from the filter if removed from the Database.
+            #[cfg(feature = "key-store")]
+            if self.is_persistent()
+                && self.auto_handles_expiry()
+                && self.config.memory.use_bloom_filters
+            {
+                let mut filter = self.filter.write().await;
+                self.inner
+                    .iter()
+                    .filter(|r| r.autoremove < current_time)
+                    .for_each(|r| filter.remove(r.key().as_bytes()));
+            }
+
+            // Without a database unloading a Session from memory is the same as it expiring.
+            let mut expired = Vec::new();
+            let mut purged = 0;
+
+            self.inner.retain(|k, v| {
+                let keep = v.autoremove > current_time;
+
+                if !keep {
+                    purged += 1;
+                    self.memory_used
+                        .fetch_sub(v.counted_size, Ordering::Relaxed);
+
+                    if !self.is_persistent() {
+                        expired.push(k.clone());
+                    }
+                }
+
+                keep
+            });
+
+            session_metrics::increment(
+                session_metrics::MEMORY_PURGED,
+                &self.config.database.table_name,
+                purged,
+            );
+            self.notify_expire(&expired).await;
+
+            self.timers.write().await.last_expiry_sweep =
+                Utc::now() + self.config.memory.purge_update;
+            logging::log(
+                self.config.log_level,
+                None,
+                "Session Memory Cleaning Finished",
+            );
+        }
+
+        // Throttle by database lifespan - e.g. sweep every 6 hours
+        // Expired Sessions are left for the database to clean up once it is available again.
+        if last_database_sweep <= current_time && self.is_persistent() && !self.is_degraded() {
+            logging::log(
+                self.config.log_level,
+                None,
+                "Session Database Cleaning Started",
+            );
+            //Remove any old keys that expired and Remove them from our loaded filter.
+            match self.cleanup().await {
+                #[cfg(feature = "key-store")]
+                Ok(expired) => {
+                    if !self.auto_handles_expiry() {
+                        let mut filter = self.filter.write().await;
+                        expired.iter().for_each(|id| filter.remove(id.as_bytes()));
+                    }
+                }
+                #[cfg(not(feature = "key-store"))]
+                Ok(_) => {}
+                Err(err) if self.degrade(&err).await => {}
+                Err(err) => return Err(err),
+            }
+
+            self.timers.write().await.last_database_expiry_sweep =
+                Utc::now() + self.config.database.purge_database_update;
+            logging::log(
+                self.config.log_level,
+                None,
+                "Session Database Cleaning Finished",
+            );
         }
 
         Ok(())
@@ -354,7 +1628,137 @@ where
                 .for_each(|value| filter.remove(value.key().as_bytes()));
         }
 
-        self.inner.clear();
+        self.inner.retain(|_, session| {
+            self.memory_used
+                .fetch_sub(session.counted_size, Ordering::Relaxed);
+            false
+        });
+    }
+
+    /// Inserts a session into memory and counts its size within the memory total.
+    pub(crate) fn memory_insert(&self, mut session: SessionData) {
+        self.memory_count(&mut session);
+
+        if let Some(replaced) = self.inner.insert(session.id.clone(), session) {
+            self.memory_used
+                .fetch_sub(replaced.counted_size, Ordering::Relaxed);
+        }
+    }
+
+    /// Adds the size of a session that is being inserted into memory to the memory total.
+    pub(crate) fn memory_count(&self, session: &mut SessionData) {
+        session.counted_size = session.memory_size();
+        self.memory_used
+            .fetch_add(session.counted_size, Ordering::Relaxed);
+    }
+
+    /// Removes a session from memory and from the memory total.
+    pub(crate) fn memory_remove(&self, id: &str) -> Option<SessionData> {
+        let (_, session) = self.inner.remove(id)?;
+        self.memory_used
+            .fetch_sub(session.counted_size, Ordering::Relaxed);
+        Some(session)
+    }
+
+    /// Updates the memory total with the size of a session that a request changed.
+    pub(crate) fn memory_recount(&self, id: &str) {
+        if let Some(mut session) = self.inner.get_mut(id) {
+            let size = session.memory_size();
+            self.memory_used.fetch_add(size, Ordering::Relaxed);
+            self.memory_used
+                .fetch_sub(session.counted_size, Ordering::Relaxed);
+            session.counted_size = size;
+        }
+    }
+
+    /// Evicts the least recently used sessions from memory once the memory capacity is reached.
+    /// Sessions with requests in flight are never evicted. Evicted sessions are loaded
+    /// from the database again on their next request.
+    ///
+    pub(crate) async fn enforce_memory_capacity(&self) {
+        let Some(capacity) = self.config.memory.capacity else {
+            return;
+        };
+
+        if !self.can_evict() {
+            return;
+        }
+
+        let (mut used, limit) = match capacity {
+            MemoryCapacity::Entries(limit) => (self.inner.len(), limit),
+            MemoryCapacity::Bytes(limit) => (self.memory_used.load(Ordering::Relaxed), limit),
+        };
+
+        if used <= limit || self.timers.read().await.next_eviction_scan > Utc::now() {
+            return;
+        }
+
+        // Free an extra sixteenth so the scan does not run again on the next request.
+        let target = limit - limit / 16;
+
+        // autoremove moves forward on every access so the earliest were used the longest ago.
+        let mut idle: Vec<(DateTime<Utc>, String, usize)> = self
+            .inner
+            .iter()
+            .filter(|r| !r.is_parallel())
+            .map(|r| {
+                let size = match capacity {
+                    MemoryCapacity::Entries(_) => 1,
+                    MemoryCapacity::Bytes(_) => r.counted_size,
+                };
+
+                (r.autoremove, r.key().clone(), size)
+            })
+            .collect();
+
+        // Every Session is in flight so wait a moment before scanning them all again.
+        if idle.is_empty() {
+            self.timers.write().await.next_eviction_scan =
+                Utc::now() + Duration::try_seconds(1).unwrap_or_default();
+            return;
+        }
+
+        idle.sort_unstable();
+
+        let mut evicted = Vec::new();
+
+        for (_, id, size) in idle {
+            if used <= target {
+                break;
+            }
+
+            // A request could have started using it since the scan.
+            if let Some((_, session)) = self
+                .inner
+                .remove_if(&id, |_, session| !session.is_parallel())
+            {
+                self.memory_used
+                    .fetch_sub(session.counted_size, Ordering::Relaxed);
+                used = used.saturating_sub(size);
+                evicted.push(id);
+            }
+        }
+
+        // Only unload these from the filter if the database removes them on its own.
+        #[cfg(feature = "key-store")]
+        if self.auto_handles_expiry() && self.config.memory.use_bloom_filters {
+            let mut filter = self.filter.write().await;
+            evicted.iter().for_each(|id| filter.remove(id.as_bytes()));
+        }
+
+        session_metrics::increment(
+            session_metrics::MEMORY_EVICTED,
+            &self.config.database.table_name,
+            evicted.len() as u64,
+        );
+        logging::log(
+            self.config.log_level,
+            None,
+            &format!(
+                "{} Sessions were evicted from memory as its capacity was reached.",
+                evicted.len()
+            ),
+        );
     }
 
     /// Attempts to load check and clear Data.
@@ -451,6 +1855,62 @@ where
         }
     }
 
+    #[inline]
+    pub(crate) fn csrf_token(&self, id: String) -> String {
+        if let Some(mut instance) = self.inner.get_mut(&id) {
+            instance.csrf_token()
+        } else {
+            tracing::warn!("Session data unexpectedly missing");
+            String::new()
+        }
+    }
+
+    #[inline]
+    pub(crate) fn set_owner(&self, id: String, owner: Option<String>) {
+        if let Some(mut instance) = self.inner.get_mut(&id) {
+            instance.set_owner(owner);
+        } else {
+            tracing::warn!("Session data unexpectedly missing");
+        }
+    }
+
+    #[inline]
+    pub(crate) fn login(&self, id: String, principal: String) {
+        if let Some(mut instance) = self.inner.get_mut(&id) {
+            instance.login(principal, self.config.clear_on_login);
+        } else {
+            tracing::warn!("Session data unexpectedly missing");
+        }
+    }
+
+    #[inline]
+    pub(crate) fn logout(&self, id: String) {
+        if let Some(mut instance) = self.inner.get_mut(&id) {
+            instance.logout();
+        } else {
+            tracing::warn!("Session data unexpectedly missing");
+        }
+    }
+
+    #[inline]
+    pub(crate) fn flash(&self, id: String, level: FlashLevel, message: String) {
+        if let Some(mut instance) = self.inner.get_mut(&id) {
+            instance.flash(level, message);
+        } else {
+            tracing::warn!("Session data unexpectedly missing");
+        }
+    }
+
+    #[inline]
+    pub(crate) fn take_flashes(&self, id: String) -> Vec<FlashMessage> {
+        if let Some(mut instance) = self.inner.get_mut(&id) {
+            instance.take_flashes()
+        } else {
+            tracing::warn!("Session data unexpectedly missing");
+            Vec::new()
+        }
+    }
+
     #[inline]
     pub(crate) fn remove(&self, id: String, key: &str) {
         if let Some(mut instance) = self.inner.get_mut(&id) {
@@ -535,11 +1995,7 @@ where
     #[inline]
     pub(crate) fn update_database_expires(&self, id: String) -> Result<(), SessionError> {
         if let Some(mut instance) = self.inner.get_mut(&id) {
-            if instance.longterm {
-                instance.expires = Utc::now() + self.config.max_lifespan;
-            } else {
-                instance.expires = Utc::now() + self.config.lifespan;
-            }
+            instance.expires = instance.next_expiry(&self.config);
 
             Ok(())
         } else {
@@ -585,7 +2041,7 @@ where
         };
 
         if is_parallel {
-            let _ = self.inner.remove(&id);
+            self.memory_remove(&id);
         }
 
         Ok(())
@@ -593,12 +2049,328 @@ where
 
     #[inline]
     pub(crate) async fn database_remove_session(&self, id: String) -> Result<(), SessionError> {
+        if self.is_degraded() {
+            self.queue_write(id, PendingWrite::Remove);
+            return Ok(());
+        }
+
+        // A queued store still being replayed is replaced so it can not bring the Session back.
+        if let Some(mut queued) = self.degraded.pending.get_mut(&id) {
+            *queued = (self.write_order(), PendingWrite::Remove);
+            return Ok(());
+        }
+
+        match self.remove_from_database(&id).await {
+            Err(err) if self.degrade(&err).await => {
+                self.queue_write(id, PendingWrite::Remove);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// private internal function that only extends when a session expires within the database.
+    ///
+    /// Returns Ok(false) if the session still needs to be fully stored.
+    /// While degraded nothing is done as the queued store sets the expiry.
+    ///
+    /// # Errors
+    /// - ['SessionError::Sqlx'] is returned if database connection has failed or user does not have permissions.
+    ///
+    pub(crate) async fn touch_session(&self, id: &str, expires: i64) -> Result<bool, SessionError> {
+        let Some(client) = &self.client else {
+            return Ok(true);
+        };
+
+        if self.is_degraded() {
+            return Ok(true);
+        }
+
+        match client
+            .touch(id, expires, &self.config.database.table_name)
+            .await
+            .map_err(SessionError::from)
+        {
+            Err(err) if self.degrade(&err).await => Ok(true),
+            result => result,
+        }
+    }
+
+    async fn remove_from_database(&self, id: &str) -> Result<(), SessionError> {
         if let Some(client) = &self.client {
             client
-                .delete_one_by_id(&id, &self.config.database.table_name)
+                .delete_one_by_id(id, &self.config.database.table_name)
                 .await?;
         }
 
         Ok(())
     }
+
+    /// Checks if a Session ID is already used within the database.
+    /// While degraded only the queued writes are checked.
+    pub(crate) async fn exists_in_database(&self, id: &str) -> Result<bool, SessionError> {
+        if self.degraded.pending.contains_key(id) {
+            return Ok(true);
+        }
+
+        let Some(client) = &self.client else {
+            return Ok(false);
+        };
+
+        if self.is_degraded() {
+            return Ok(false);
+        }
+
+        match client
+            .exists(id, &self.config.database.table_name)
+            .await
+            .map_err(SessionError::from)
+        {
+            Err(err) if self.degrade(&err).await => Ok(false),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        databases::mock::MockPool, ConflictPolicy, DatabaseFailurePolicy, Key, MemoryCapacity,
+        SessionConfig, SessionData, SessionNullPool, SessionStore,
+    };
+    use chrono::{Duration, Utc};
+    use std::sync::atomic::Ordering;
+
+    async fn degraded_store(pool: &MockPool) -> SessionStore<MockPool> {
+        let config = SessionConfig::default()
+            .with_failure_policy(DatabaseFailurePolicy::ServeFromMemory(Duration::zero()));
+        let store = SessionStore::new(Some(pool.clone()), config).await.unwrap();
+        pool.set_failing(true);
+        store
+    }
+
+    #[tokio::test]
+    async fn reencrypt_keeps_stored_expiry() {
+        let pool = MockPool::default();
+        let old_key = Key::generate();
+        let config = SessionConfig::default().with_database_key(old_key.clone());
+        let store = SessionStore::new(Some(pool.clone()), config).await.unwrap();
+
+        let mut session = SessionData::new("id".to_owned(), true, &store.config);
+        session.expires = Utc::now() + Duration::minutes(5);
+        store.store_session(&session).await.unwrap();
+        let before = pool.row("id").unwrap();
+
+        let config = SessionConfig::default()
+            .with_database_key(Key::generate())
+            .with_retired_database_key(old_key);
+        let store = SessionStore::new(Some(pool.clone()), config).await.unwrap();
+
+        assert_eq!(store.reencrypt_all().await.unwrap(), 1);
+        let after = pool.row("id").unwrap();
+        assert_ne!(after.session, before.session);
+        assert_eq!(after.expires, before.expires);
+    }
+
+    #[tokio::test]
+    async fn retired_key_loads_are_fully_stored() {
+        let pool = MockPool::default();
+        let old_key = Key::generate();
+        let config = SessionConfig::default().with_database_key(old_key.clone());
+        let store = SessionStore::new(Some(pool.clone()), config).await.unwrap();
+        let session = SessionData::new("id".to_owned(), true, &store.config);
+        store.store_session(&session).await.unwrap();
+
+        let loaded = store.load_session("id".to_owned()).await.unwrap().unwrap();
+        assert!(!loaded.update && !loaded.full_write);
+
+        let config = SessionConfig::default()
+            .with_database_key(Key::generate())
+            .with_retired_database_key(old_key);
+        let store = SessionStore::new(Some(pool.clone()), config).await.unwrap();
+        let loaded = store.load_session("id".to_owned()).await.unwrap().unwrap();
+        assert!(loaded.update && loaded.full_write);
+    }
+
+    #[tokio::test]
+    async fn stores_are_seen_by_versioned_writers() {
+        let pool = MockPool::default();
+        let config = SessionConfig::default().with_conflict_policy(ConflictPolicy::Error);
+        let versioned = SessionStore::new(Some(pool.clone()), config).await.unwrap();
+        let last_write_wins = SessionStore::new(Some(pool.clone()), SessionConfig::default())
+            .await
+            .unwrap();
+
+        let session = SessionData::new("id".to_owned(), true, &versioned.config);
+        assert!(versioned.compare_and_store_session(&session).await.unwrap());
+        let stale = versioned
+            .load_session("id".to_owned())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let mut session = last_write_wins
+            .load_session("id".to_owned())
+            .await
+            .unwrap()
+            .unwrap();
+        session.set("key", 1);
+        session.expires = Utc::now() + Duration::minutes(5);
+        last_write_wins.store_session(&session).await.unwrap();
+
+        assert!(!versioned.compare_and_store_session(&stale).await.unwrap());
+        let fresh = versioned
+            .load_session("id".to_owned())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(fresh.version, 2);
+        assert!(versioned.compare_and_store_session(&fresh).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn partial_store_falls_back_to_store() {
+        let pool = MockPool::default();
+        let store = SessionStore::new(Some(pool.clone()), SessionConfig::default())
+            .await
+            .unwrap();
+
+        let mut session = SessionData::new("id".to_owned(), true, &store.config);
+        store.save_session(session.clone(), None).await.unwrap();
+        session.reset_changes();
+        assert_eq!(pool.take_writes(), vec!["store:id"]);
+
+        session.set("key", 1);
+        store.save_session(session, None).await.unwrap();
+        assert_eq!(pool.take_writes(), vec!["store_partial:id", "store:id"]);
+    }
+
+    #[tokio::test]
+    async fn degraded_writes_replay_in_order() {
+        let pool = MockPool::default();
+        let store = degraded_store(&pool).await;
+
+        for id in ["c", "a", "b"] {
+            let session = SessionData::new(id.to_owned(), true, &store.config);
+            store.save_session(session, None).await.unwrap();
+        }
+
+        assert!(store.is_degraded());
+        assert_eq!(store.pending_writes(), 3);
+
+        pool.set_failing(false);
+        store.retry_database().await;
+
+        assert!(!store.is_degraded());
+        assert_eq!(store.pending_writes(), 0);
+        assert_eq!(pool.take_writes(), vec!["store:c", "store:a", "store:b"]);
+    }
+
+    #[tokio::test]
+    async fn replay_does_not_overwrite_newer_write() {
+        let pool = MockPool::default();
+        let store = degraded_store(&pool).await;
+
+        let mut session = SessionData::new("id".to_owned(), true, &store.config);
+        session.set("value", 1);
+        store.save_session(session.clone(), None).await.unwrap();
+
+        // The database is back but the queued write was not replayed yet.
+        pool.set_failing(false);
+        store.degraded.active.store(false, Ordering::Release);
+
+        session.set("value", 2);
+        store.save_session(session, None).await.unwrap();
+        assert!(pool.take_writes().is_empty());
+
+        // The replay `retry_database` runs after leaving degraded mode.
+        assert!(store.replay_pending().await);
+        assert_eq!(store.pending_writes(), 0);
+        assert_eq!(pool.take_writes(), vec!["store:id"]);
+
+        let stored = store.load_session("id".to_owned()).await.unwrap().unwrap();
+        assert_eq!(stored.get::<i32>("value"), Some(2));
+    }
+
+    #[tokio::test]
+    async fn degraded_owner_lookup_uses_memory() {
+        let pool = MockPool::default();
+        let store = degraded_store(&pool).await;
+
+        let mut session = SessionData::new("id".to_owned(), true, &store.config);
+        session.set_owner(Some("user".to_owned()));
+        store.save_session(session, None).await.unwrap();
+        assert!(store.is_degraded());
+
+        assert_eq!(store.list_by_owner("user").await.unwrap(), vec!["id"]);
+    }
+
+    #[tokio::test]
+    async fn memory_total_follows_sessions() {
+        let store = SessionStore::new(Some(MockPool::default()), SessionConfig::default())
+            .await
+            .unwrap();
+        let total = || store.memory_used.load(Ordering::Relaxed);
+        let sum = || -> usize { store.inner.iter().map(|r| r.memory_size()).sum() };
+
+        store.memory_insert(SessionData::new("a".to_owned(), true, &store.config));
+        store.memory_insert(SessionData::new("b".to_owned(), true, &store.config));
+        assert_eq!(total(), sum());
+
+        store
+            .inner
+            .get_mut("a")
+            .unwrap()
+            .set("key", "x".repeat(100));
+        store.memory_recount("a");
+        assert_eq!(total(), sum());
+
+        store.memory_insert(SessionData::new("a".to_owned(), true, &store.config));
+        assert_eq!(total(), sum());
+
+        store.memory_remove("a");
+        store.memory_remove("b");
+        assert_eq!(total(), 0);
+    }
+
+    #[tokio::test]
+    async fn in_flight_sessions_are_never_evicted() {
+        let config = SessionConfig::default().with_memory_capacity(MemoryCapacity::Bytes(1));
+        let store = SessionStore::new(Some(MockPool::default()), config)
+            .await
+            .unwrap();
+
+        // New sessions start with a request in flight.
+        store.memory_insert(SessionData::new("busy".to_owned(), true, &store.config));
+        store.memory_insert(SessionData::new("idle".to_owned(), true, &store.config));
+        store.inner.get_mut("idle").unwrap().remove_request();
+
+        store.enforce_memory_capacity().await;
+        assert!(store.inner.contains_key("busy"));
+        assert!(!store.inner.contains_key("idle"));
+        assert_eq!(
+            store.memory_used.load(Ordering::Relaxed),
+            store.inner.get("busy").unwrap().memory_size()
+        );
+
+        // Nothing is left to evict so the next scan waits.
+        store.enforce_memory_capacity().await;
+        assert!(store.timers.read().await.next_eviction_scan > Utc::now());
+    }
+
+    #[tokio::test]
+    async fn sessions_are_never_evicted_without_a_database() {
+        let config = SessionConfig::default().with_memory_capacity(MemoryCapacity::Entries(1));
+        let store = SessionStore::new(Some(SessionNullPool), config)
+            .await
+            .unwrap();
+
+        for id in ["a", "b"] {
+            store.memory_insert(SessionData::new(id.to_owned(), true, &store.config));
+            store.inner.get_mut(id).unwrap().remove_request();
+        }
+
+        store.enforce_memory_capacity().await;
+        assert_eq!(store.inner.len(), 2);
+    }
 }
@@ -0,0 +1,108 @@
+use axum::{
+    body::Body,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use http::{
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+    HeaderValue,
+};
+use http_body::Frame;
+use std::{
+    convert::Infallible,
+    pin::Pin,
+    sync::{Arc, OnceLock},
+    task::{Context, Poll},
+};
+
+/// Filled in by the `SessionService` once the Session ID can no longer change.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TokenSlot(pub(crate) Arc<OnceLock<Option<String>>>);
+
+/// Responds with the signed Session token as JSON so clients using
+/// `SessionTransport::Header` can read it without any extra CORS setup.
+///
+/// The token is written after the `SessionLayer` is done with the Session, so it
+/// is the renewed token if the handler called `Session::renew`. The body is
+/// `{"access_token": "<token>", "token_type": "Bearer"}` and `access_token` is null
+/// if the Session was destroyed.
+///
+/// # Examples
+/// ```rust ignore
+/// use axum_session::{Session, SessionNullPool, SessionToken};
+///
+/// async fn login(session: Session<SessionNullPool>) -> SessionToken {
+///     session.renew();
+///     SessionToken::new()
+/// }
+/// ```
+///
+#[derive(Debug, Clone, Default)]
+pub struct SessionToken {
+    slot: TokenSlot,
+}
+
+impl SessionToken {
+    /// Constructs a new SessionToken response.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axum_session::SessionToken;
+    ///
+    /// let token = SessionToken::new();
+    /// ```
+    ///
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IntoResponse for SessionToken {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::new(TokenBody {
+            slot: self.slot.clone(),
+            done: false,
+        }));
+
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        response.extensions_mut().insert(self.slot);
+        response
+    }
+}
+
+/// Body that only reads the token when it is sent so the Session ID is final.
+struct TokenBody {
+    slot: TokenSlot,
+    done: bool,
+}
+
+impl http_body::Body for TokenBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        self.done = true;
+
+        let token = self.slot.0.get().cloned().flatten();
+        let body = serde_json::json!({
+            "access_token": token,
+            "token_type": "Bearer",
+        });
+
+        Poll::Ready(Some(Ok(Frame::data(Bytes::from(body.to_string())))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+}